@@ -1 +1,50 @@
-pub mod postgres;
+//! Derive macros and the `fmt_query!` macro for `sqlink`
+extern crate proc_macro;
+mod postgres;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// generate `sql_insert(&self) -> SqlInsert`, which `set()` every field of the struct
+/// ```ignore
+/// #[derive(SqlInsert)]
+/// #[sqlink(table = "person")]
+/// struct PersonForm {
+///     id: i32,
+///     #[sqlink(rename = "name")]
+///     person_name: String,
+///     #[sqlink(skip)]
+///     unused: bool,
+/// }
+/// let qbuilt = person_form.sql_insert().returning("id").build().unwrap();
+/// ```
+#[proc_macro_derive(SqlInsert, attributes(sqlink))]
+pub fn derive_sql_insert(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    postgres::derive_sql_insert(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// generate `sql_update(&self) -> SqlUpdate`, which `set()` every field of the struct, the where clause is up to the caller
+#[proc_macro_derive(SqlUpdate, attributes(sqlink))]
+pub fn derive_sql_update(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    postgres::derive_sql_update(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// generate `from_row<R: RowGet>(row: &R) -> Self`, which read every field from the column with the same name,
+/// skipped fields are filled with `Default::default()`.
+/// `sqlink::postgres::RowGet` is implemented for `postgres::Row` / `tokio_postgres::Row` with the `tokio-postgres` feature of sqlink
+#[proc_macro_derive(SqlRow, attributes(sqlink))]
+pub fn derive_sql_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    postgres::derive_sql_row(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// same as `sqlink::postgres::format_query`, but the number of `{}` is checked at compile time
+/// ```ignore
+/// sqlselect.and_where(fmt_query!("u.id = {} AND u.name = {}", 1, "foo"));
+/// ```
+#[proc_macro]
+pub fn fmt_query(input: TokenStream) -> TokenStream {
+    postgres::fmt_query(input.into()).unwrap_or_else(|e| e.to_compile_error()).into()
+}
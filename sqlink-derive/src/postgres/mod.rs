@@ -1,41 +1,195 @@
-#[macro_export]
-macro_rules! fmt_query {
-    ($a:literal) => {
-        sqlink::format_query_postgres($a.into(), vec![])
-    };
-    ($a:literal, $($x:tt), *) => {
-        sqlink::format_query_postgres($a.into(), vec![$(&($x)), *])
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Data, DeriveInput, Error, Expr, Fields, Ident, Lit, LitStr, Meta, NestedMeta, Token};
+
+struct SqlField {
+    ident: Ident,
+    column: String,
+    skip: bool,
+}
+
+fn sqlink_metas(attrs: &[syn::Attribute]) -> Result<Vec<NestedMeta>, Error> {
+    let mut metas = Vec::new();
+    for attr in attrs {
+        if !attr.path.is_ident("sqlink") {
+            continue;
+        }
+        match attr.parse_meta()? {
+            Meta::List(list) => metas.extend(list.nested),
+            other => return Err(Error::new_spanned(other, "expected #[sqlink(...)]")),
+        }
+    }
+    Ok(metas)
+}
+
+fn table_name(input: &DeriveInput) -> Result<Option<String>, Error> {
+    let mut table = None;
+    for meta in sqlink_metas(&input.attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("table") => {
+                match nv.lit {
+                    Lit::Str(s) => table = Some(s.value()),
+                    other => return Err(Error::new_spanned(other, "table has to be a string")),
+                }
+            },
+            other => return Err(Error::new_spanned(other, "unknown sqlink attribute, expected table = \"...\"")),
+        }
+    }
+    Ok(table)
+}
+
+fn sql_fields(input: &DeriveInput) -> Result<Vec<SqlField>, Error> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(Error::new_spanned(&input.ident, "sqlink derive only support struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "sqlink derive only support struct")),
     };
+    let mut fields = Vec::new();
+    for field in named {
+        let ident = field.ident.clone().expect("named field");
+        let mut column = ident.to_string();
+        let mut skip = false;
+        for meta in sqlink_metas(&field.attrs)? {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    match nv.lit {
+                        Lit::Str(s) => column = s.value(),
+                        other => return Err(Error::new_spanned(other, "rename has to be a string")),
+                    }
+                },
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => skip = true,
+                other => return Err(Error::new_spanned(other, "unknown sqlink attribute, expected rename = \"...\" or skip")),
+            }
+        }
+        fields.push(SqlField {
+            ident,
+            column,
+            skip,
+        });
+    }
+    Ok(fields)
+}
+
+fn derive_builder(input: &DeriveInput, method: TokenStream, builder: TokenStream) -> Result<TokenStream, Error> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let table = table_name(input)?.map(|table| quote! { builder.table(#table); });
+    let fields = sql_fields(input)?;
+    let sets = fields.iter().filter(|f| !f.skip).map(|f| {
+        let ident = &f.ident;
+        let column = &f.column;
+        quote! { builder.set(#column, &self.#ident); }
+    });
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn #method(&self) -> #builder<'_> {
+                let mut builder: #builder<'_> = #builder::new();
+                #table
+                #(#sets)*
+                builder
+            }
+        }
+    })
+}
+
+pub fn derive_sql_insert(input: &DeriveInput) -> Result<TokenStream, Error> {
+    derive_builder(input, quote!(sql_insert), quote!(sqlink::postgres::SqlInsert))
 }
 
+pub fn derive_sql_update(input: &DeriveInput) -> Result<TokenStream, Error> {
+    derive_builder(input, quote!(sql_update), quote!(sqlink::postgres::SqlUpdate))
+}
+
+pub fn derive_sql_row(input: &DeriveInput) -> Result<TokenStream, Error> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // table is only used by the builders, but still validate the attribute
+    table_name(input)?;
+    let fields = sql_fields(input)?;
+    let inits = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let column = &f.column;
+        if f.skip {
+            quote! { #ident: ::std::default::Default::default() }
+        } else {
+            quote! { #ident: sqlink::postgres::RowGet::get_column(row, #column) }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn from_row<R: sqlink::postgres::RowGet>(row: &R) -> Self {
+                #name {
+                    #(#inits,)*
+                }
+            }
+        }
+    })
+}
+
+struct FmtQueryInput {
+    query: LitStr,
+    args: Vec<Expr>,
+}
+
+impl Parse for FmtQueryInput {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let query: LitStr = input.parse()?;
+        let mut args = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            args.push(input.parse()?);
+        }
+        Ok(FmtQueryInput {
+            query,
+            args,
+        })
+    }
+}
+
+// same scanning rule as sqlink::postgres::format_query
+fn count_placeholder(query: &str) -> usize {
+    let chars: Vec<char> = query.chars().collect();
+    let mut count = 0;
+    let mut cur = 0;
+    while cur < chars.len() {
+        if chars[cur] == '{' && cur + 1 < chars.len() && chars[cur + 1] == '}' {
+            count += 1;
+            cur += 2;
+        } else {
+            cur += 1;
+        }
+    }
+    count
+}
+
+pub fn fmt_query(input: TokenStream) -> Result<TokenStream, Error> {
+    let FmtQueryInput { query, args } = syn::parse2(input)?;
+    let expected = count_placeholder(&query.value());
+    if expected != args.len() {
+        return Err(Error::new_spanned(
+            &query,
+            format!("query has {} placeholder but {} argument is given", expected, args.len()),
+        ));
+    }
+    Ok(quote! {
+        sqlink::postgres::format_query(#query, vec![#(&(#args)),*])
+    })
+}
 
 #[cfg(test)]
 mod tests {
-    use sqlink::{PostgresBuilder};
-    #[test]
-    fn test_insert_builder_derive() {
-        let user_spouse: Option<String> = None;
-        let mut sql_insert = PostgresBuilder::insert();
-        let qbuild = sql_insert
-            .table("user")
-            .set("spouse", &user_spouse)
-            .set("age", &1337)
-            .set_raw("name", fmt_query!("LOWER({})", "foo"))
-            .build().unwrap();
-        assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"spouse\",\"age\",\"name\") VALUES ($1,$2,LOWER($3))");
-        assert_eq!(format!("{:?}", qbuild.parameters), "[None, 1337, \"foo\"]");
-    }
+    use super::*;
     #[test]
-    fn test_select_builder_derive() {
-        let mut sql_select = PostgresBuilder::select();
-        let qbuild = sql_select
-            .select("u.id")
-            .select("ub.account_no")
-            .and_where(fmt_query!("u.id = {}", 1))
-            .table(("user", "u"))
-            .left_join(("user_bank", "ub"), fmt_query!("u.user_id = ub.user_id and ub.code = {}", "abc"))
-            .build().unwrap();
-        assert_eq!(qbuild.query, "SELECT u.id, ub.account_no FROM \"user\" AS u LEFT JOIN \"user_bank\" AS ub ON u.user_id = ub.user_id and ub.code = $1 WHERE u.id = $2");
-        assert_eq!(format!("{:?}", qbuild.parameters), "[\"abc\", 1]");
+    fn test_count_placeholder() {
+        assert_eq!(count_placeholder("id = {}"), 1);
+        assert_eq!(count_placeholder("a = {} AND b = LOWER({})"), 2);
+        assert_eq!(count_placeholder("a = '{'"), 0);
+        assert_eq!(count_placeholder("{}}{}"), 2);
     }
 }
@@ -0,0 +1,132 @@
+use sqlink::PostgresBuilder;
+use std::collections::HashMap;
+use postgres_types::{FromSql, Type};
+use sqlink::postgres::RowGet;
+use sqlink_derive::{fmt_query, SqlInsert, SqlRow, SqlUpdate};
+
+#[derive(SqlInsert, SqlUpdate)]
+#[sqlink(table = "person")]
+struct PersonForm {
+    id: i32,
+    #[sqlink(rename = "name")]
+    person_name: String,
+    data: Option<Vec<u8>>,
+    #[sqlink(skip)]
+    #[allow(dead_code)]
+    confirmed: bool,
+}
+
+#[derive(Debug, PartialEq, SqlRow)]
+struct PersonRow {
+    id: i32,
+    #[sqlink(rename = "name")]
+    person_name: String,
+    data: Option<Vec<u8>>,
+    #[sqlink(skip)]
+    confirmed: bool,
+}
+
+/// a row decoded from the binary wire format, like the row of a postgres client
+struct MockRow(HashMap<&'static str, (Type, Option<Vec<u8>>)>);
+
+impl RowGet for MockRow {
+    fn get_column<'b, T: FromSql<'b>>(&'b self, column: &str) -> T {
+        let (ty, raw) = &self.0[column];
+        match raw {
+            Some(raw) => T::from_sql(ty, raw).unwrap(),
+            None => T::from_sql_null(ty).unwrap(),
+        }
+    }
+}
+
+#[derive(SqlInsert)]
+struct PersonNameForm<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_insert_builder_derive() {
+    let user_spouse: Option<String> = None;
+    let mut sql_insert = PostgresBuilder::insert();
+    let qbuild = sql_insert
+        .table("user")
+        .set("spouse", &user_spouse)
+        .set("age", &1337)
+        .set_raw("name", fmt_query!("LOWER({})", "foo"))
+        .build().unwrap();
+    assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"spouse\",\"age\",\"name\") VALUES ($1,$2,LOWER($3))");
+    assert_eq!(format!("{:?}", qbuild.parameters), "[None, 1337, \"foo\"]");
+}
+
+#[test]
+fn test_select_builder_derive() {
+    let mut sql_select = PostgresBuilder::select();
+    let qbuild = sql_select
+        .select("u.id")
+        .select("ub.account_no")
+        .and_where(fmt_query!("u.id = {}", 1))
+        .table(("user", "u"))
+        .left_join(("user_bank", "ub"), fmt_query!("u.user_id = ub.user_id and ub.code = {}", "abc"))
+        .build().unwrap();
+    assert_eq!(qbuild.query, "SELECT u.id, ub.account_no FROM \"user\" AS u LEFT JOIN \"user_bank\" AS ub ON u.user_id = ub.user_id and ub.code = $1 WHERE u.id = $2");
+    assert_eq!(format!("{:?}", qbuild.parameters), "[\"abc\", 1]");
+}
+
+#[test]
+fn test_derive_sql_insert() {
+    let person_form = PersonForm {
+        id: 3,
+        person_name: "Hello World".to_owned(),
+        data: None,
+        confirmed: true,
+    };
+    let mut sqlinsert = person_form.sql_insert();
+    let qbuild = sqlinsert
+        .returning("id")
+        .build().unwrap();
+    assert_eq!(qbuild.query, "INSERT INTO \"person\"(\"id\",\"name\",\"data\") VALUES ($1,$2,$3) RETURNING id");
+    assert_eq!(format!("{:?}", qbuild.parameters), "[3, \"Hello World\", None]");
+}
+
+#[test]
+fn test_derive_sql_insert_without_table() {
+    let form = PersonNameForm {
+        name: "foo",
+    };
+    let mut sqlinsert = form.sql_insert();
+    let qbuild = sqlinsert
+        .table(("public", "person", "p"))
+        .build().unwrap();
+    assert_eq!(qbuild.query, "INSERT INTO \"public\".\"person\" AS p(\"name\") VALUES ($1)");
+}
+
+#[test]
+fn test_derive_sql_update() {
+    let person_form = PersonForm {
+        id: 3,
+        person_name: "Hello World".to_owned(),
+        data: None,
+        confirmed: false,
+    };
+    let mut sqlupdate = person_form.sql_update();
+    let qbuild = sqlupdate
+        .and_where(fmt_query!("id = {}", person_form.id))
+        .build().unwrap();
+    assert_eq!(qbuild.query, "UPDATE \"person\" SET \"id\"=$1,\"name\"=$2,\"data\"=$3 WHERE id = $4");
+    assert_eq!(format!("{:?}", qbuild.parameters), "[3, \"Hello World\", None, 3]");
+}
+
+#[test]
+fn test_derive_sql_row() {
+    let mut columns = HashMap::new();
+    columns.insert("id", (Type::INT4, Some(3i32.to_be_bytes().to_vec())));
+    columns.insert("name", (Type::VARCHAR, Some(b"Hello World".to_vec())));
+    columns.insert("data", (Type::BYTEA, None));
+    let person = PersonRow::from_row(&MockRow(columns));
+    assert_eq!(person, PersonRow {
+        id: 3,
+        person_name: "Hello World".to_owned(),
+        data: None,
+        confirmed: false,
+    });
+}
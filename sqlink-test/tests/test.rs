@@ -4,13 +4,17 @@ use sqlink::{
     PostgresBuilder,
     postgres::{format_query, op}
 };
-#[derive(Debug, PartialEq)]
+use sqlink_derive::{SqlInsert, SqlRow};
+#[derive(Debug, PartialEq, SqlRow)]
 struct Person {
     id: i32,
+    #[sqlink(rename = "person_name")]
     name: String,
     data: Option<Vec<u8>>
 }
 
+#[derive(SqlInsert)]
+#[sqlink(table = "person")]
 struct PersonForm {
     id: i32,
     name: String,
@@ -32,18 +36,14 @@ fn test_postgres_db() -> Result<(), PostgresError> {
         name: "Hello World".to_owned(),
         data: None
     };
-    let mut sqlinsert = PostgresBuilder::insert();
+    let mut sqlinsert = person_form.sql_insert(); // equivalence to table("person") and set() each field of PersonForm
     let qbuiltinsert = sqlinsert
-        .table("person")
         .returning("id")
-        .set("id", &person_form.id)
-        .set("name", &person_form.name)
-        .set("data", &person_form.data)
         .build().unwrap();
     let mut id: i32 = 0;
     for row in &conn.query(
         qbuiltinsert.query.as_str(),
-        &qbuiltinsert.parameters,
+        &qbuiltinsert.params(),
     )? {
         id = row.get(0);
     }
@@ -59,7 +59,7 @@ fn test_postgres_db() -> Result<(), PostgresError> {
         .build().unwrap();
     conn.query(
         qbuiltinsert.query.as_str(),
-        &qbuiltinsert.parameters,
+        &qbuiltinsert.params(),
     )?;
     let mut sqlselect = PostgresBuilder::select();
     let qbuiltselect = sqlselect
@@ -74,19 +74,13 @@ fn test_postgres_db() -> Result<(), PostgresError> {
         .order("id", "ASC")
         .build().unwrap();
     let mut person_vec: Vec<Person> = Vec::new();
-    for row in &conn.query(qbuiltselect.query.as_str(), &qbuiltselect.parameters).unwrap() {
-        person_vec.push(Person {
-            id: row.get(0),
-            name: row.get(1),
-            data: row.get(2),
-        })
+    for row in &conn.query(qbuiltselect.query.as_str(), &qbuiltselect.params()).unwrap() {
+        person_vec.push(Person::from_row(&row))
     }
-    // // misc feature
-    // sqlselect
-    //     .reset_selects()
-    //     .select("COUNT(id)")
-    //     .group("something")
-    //     .build().unwrap();
+    // same filters without ORDER BY and LIMIT, for the total of a paginated list
+    let qbuiltcount = sqlselect.count().build().unwrap();
+    let total: i64 = conn.query_one(qbuiltcount.query.as_str(), &qbuiltcount.params())?.get(0);
+    assert_eq!(total, 1);
     assert_eq!(person_vec, vec![Person {
         id: 3,
         name: "Real Hello World".to_owned(),
@@ -101,10 +95,10 @@ fn test_postgres_db() -> Result<(), PostgresError> {
         .build().unwrap();
     conn.query(
         qbuiltdelete.query.as_str(),
-        &qbuiltdelete.parameters,
+        &qbuiltdelete.params(),
     )?;
 
-    let rows = &conn.query(qbuiltselect.query.as_str(), &qbuiltselect.parameters).unwrap();
+    let rows = &conn.query(qbuiltselect.query.as_str(), &qbuiltselect.params()).unwrap();
     assert_eq!(rows.len(), 0);
     Ok(())
 }
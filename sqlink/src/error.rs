@@ -1,4 +1,293 @@
-#[derive(Debug)]
+use std::fmt;
+
+/// the builder that produced the error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuilderKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Compound,
+}
+
+impl fmt::Display for BuilderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BuilderKind::Select => "SELECT",
+            BuilderKind::Insert => "INSERT",
+            BuilderKind::Update => "UPDATE",
+            BuilderKind::Delete => "DELETE",
+            BuilderKind::Compound => "COMPOUND SELECT",
+        };
+        f.write_str(s)
+    }
+}
+
+/// the clause of the statement that produced the error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clause {
+    With,
+    Select,
+    From,
+    Join,
+    Where,
+    Set,
+    Values,
+    OnConflict,
+    Returning,
+    GroupBy,
+    Having,
+    Window,
+    OrderBy,
+    Limit,
+    Lock,
+}
+
+impl fmt::Display for Clause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Clause::With => "WITH",
+            Clause::Select => "SELECT",
+            Clause::From => "FROM",
+            Clause::Join => "JOIN",
+            Clause::Where => "WHERE",
+            Clause::Set => "SET",
+            Clause::Values => "VALUES",
+            Clause::OnConflict => "ON CONFLICT",
+            Clause::Returning => "RETURNING",
+            Clause::GroupBy => "GROUP BY",
+            Clause::Having => "HAVING",
+            Clause::Window => "WINDOW",
+            Clause::OrderBy => "ORDER BY",
+            Clause::Limit => "LIMIT",
+            Clause::Lock => "FOR",
+        };
+        f.write_str(s)
+    }
+}
+
+/// where the error happened, filled in as the error goes up to the builder
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorContext {
+    pub builder: Option<BuilderKind>,
+    pub clause: Option<Clause>,
+    /// the offending table, column, constraint or query fragment
+    pub identifier: Option<String>,
+}
+
+impl ErrorContext {
+    pub(crate) fn identifier<S: Into<String>>(identifier: S) -> Self {
+        ErrorContext {
+            builder: None,
+            clause: None,
+            identifier: Some(identifier.into()),
+        }
+    }
+    pub(crate) fn clause(clause: Clause) -> Self {
+        ErrorContext {
+            builder: None,
+            clause: Some(clause),
+            identifier: None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(builder) = &self.builder {
+            parts.push(format!("builder: {}", builder));
+        }
+        if let Some(clause) = &self.clause {
+            parts.push(format!("clause: {}", clause));
+        }
+        if let Some(identifier) = &self.identifier {
+            parts.push(format!("identifier: {}", identifier));
+        }
+        if parts.is_empty() {
+            return Ok(());
+        }
+        write!(f, " [{}]", parts.join(", "))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Error {
-    Syntax(String)
+    /// any other invalid statement
+    Syntax { message: String, context: ErrorContext },
+    /// the statement has more parameters than postgres support, hold the maximum
+    ParameterOverflow { max: usize, context: ErrorContext },
+    /// format_query / compose_query got a different number of arguments than {} in the query
+    ArgumentCountMismatch { expected: usize, given: usize, context: ErrorContext },
+    /// no table is given, or join is called before any table is given
+    MissingTable { context: ErrorContext },
+    /// the statement only support 1 table
+    TableCount { count: usize, context: ErrorContext },
+    /// join is given an empty ON condition
+    JoinWithoutOn { context: ErrorContext },
+    /// on is called without a join table
+    OnWithoutJoin { context: ErrorContext },
+    /// where_close / having_close without a matching open, or an open that is never closed
+    UnbalancedWhere { context: ErrorContext },
+    /// insert / update / DO UPDATE without any set
+    EmptySet { context: ErrorContext },
+    /// a column list that requires at least 1 column is empty
+    EmptyColumns { context: ErrorContext },
+    /// a multi-row insert row has different columns from the first row
+    RowColumnMismatch { row: usize, context: ErrorContext },
+    /// insert is given both set and from_select
+    SetWithSelect { context: ErrorContext },
+    /// ON CONFLICT without DO NOTHING or DO UPDATE
+    ConflictWithoutAction { context: ErrorContext },
+    /// ON CONFLICT WHERE or DO UPDATE without conflict columns
+    ConflictWithoutTarget { context: ErrorContext },
+    /// ON CONFLICT ON CONSTRAINT cannot have WHERE
+    ConstraintWithWhere { context: ErrorContext },
+    /// a leading ORDER BY expression is not one of the DISTINCT ON expressions
+    DistinctOnMismatch { context: ErrorContext },
+    /// a select of UNION / INTERSECT / EXCEPT has a different number of columns from the first select
+    CompoundColumnCount { member: usize, expected: usize, given: usize, context: ErrorContext },
+    /// an ORDER BY direction besides ASC / DESC and NULLS FIRST / NULLS LAST
+    InvalidOrder { context: ErrorContext },
+    /// Ident::parse is given something besides plain or double quoted names separated by .
+    InvalidIdentifier { context: ErrorContext },
+    /// Cursor::decode is given a token that is not from Cursor::encode, or a value cannot be read as the type
+    InvalidCursor { context: ErrorContext },
+    /// FOR UPDATE / FOR SHARE together with DISTINCT, GROUP BY, HAVING, WINDOW or UNION / INTERSECT / EXCEPT
+    LockNotAllowed { context: ErrorContext },
+}
+
+impl Error {
+    pub fn context(&self) -> &ErrorContext {
+        match self {
+            Error::Syntax { context, .. } => context,
+            Error::ParameterOverflow { context, .. } => context,
+            Error::ArgumentCountMismatch { context, .. } => context,
+            Error::MissingTable { context } => context,
+            Error::TableCount { context, .. } => context,
+            Error::JoinWithoutOn { context } => context,
+            Error::OnWithoutJoin { context } => context,
+            Error::UnbalancedWhere { context } => context,
+            Error::EmptySet { context } => context,
+            Error::EmptyColumns { context } => context,
+            Error::RowColumnMismatch { context, .. } => context,
+            Error::SetWithSelect { context } => context,
+            Error::ConflictWithoutAction { context } => context,
+            Error::ConflictWithoutTarget { context } => context,
+            Error::ConstraintWithWhere { context } => context,
+            Error::DistinctOnMismatch { context } => context,
+            Error::CompoundColumnCount { context, .. } => context,
+            Error::InvalidOrder { context } => context,
+            Error::InvalidIdentifier { context } => context,
+            Error::InvalidCursor { context } => context,
+            Error::LockNotAllowed { context } => context,
+        }
+    }
+    fn context_mut(&mut self) -> &mut ErrorContext {
+        match self {
+            Error::Syntax { context, .. } => context,
+            Error::ParameterOverflow { context, .. } => context,
+            Error::ArgumentCountMismatch { context, .. } => context,
+            Error::MissingTable { context } => context,
+            Error::TableCount { context, .. } => context,
+            Error::JoinWithoutOn { context } => context,
+            Error::OnWithoutJoin { context } => context,
+            Error::UnbalancedWhere { context } => context,
+            Error::EmptySet { context } => context,
+            Error::EmptyColumns { context } => context,
+            Error::RowColumnMismatch { context, .. } => context,
+            Error::SetWithSelect { context } => context,
+            Error::ConflictWithoutAction { context } => context,
+            Error::ConflictWithoutTarget { context } => context,
+            Error::ConstraintWithWhere { context } => context,
+            Error::DistinctOnMismatch { context } => context,
+            Error::CompoundColumnCount { context, .. } => context,
+            Error::InvalidOrder { context } => context,
+            Error::InvalidIdentifier { context } => context,
+            Error::InvalidCursor { context } => context,
+            Error::LockNotAllowed { context } => context,
+        }
+    }
+    /// fill in the builder and clause that are not known yet,
+    /// the innermost one wins so an error from a subquery keeps its own builder and clause
+    pub(crate) fn within(mut self, builder: BuilderKind, clause: Clause) -> Self {
+        let context = self.context_mut();
+        if context.builder.is_none() {
+            context.builder = Some(builder);
+        }
+        if context.clause.is_none() {
+            context.clause = Some(clause);
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax { message, .. } => write!(f, "{}", message)?,
+            Error::ParameterOverflow { max, .. } => write!(f, "statement has more than {} parameters", max)?,
+            Error::ArgumentCountMismatch { expected, given, .. } => {
+                write!(f, "query has {} placeholder but {} argument is given", expected, given)?
+            },
+            Error::MissingTable { .. } => f.write_str("no table is given")?,
+            Error::TableCount { count, .. } => write!(f, "statement requires 1 table but {} is given", count)?,
+            Error::JoinWithoutOn { .. } => f.write_str("join requires an ON condition")?,
+            Error::OnWithoutJoin { .. } => f.write_str("ON condition is given without a join")?,
+            Error::UnbalancedWhere { .. } => f.write_str("open and close of the where / having groups are not balanced")?,
+            Error::EmptySet { .. } => f.write_str("statement requires at least 1 set")?,
+            Error::EmptyColumns { .. } => f.write_str("column list requires at least 1 column")?,
+            Error::RowColumnMismatch { row, .. } => write!(f, "row {} columns does not match with the first row", row)?,
+            Error::SetWithSelect { .. } => f.write_str("insert cannot have both set and select")?,
+            Error::ConflictWithoutAction { .. } => f.write_str("ON CONFLICT requires DO NOTHING or DO UPDATE")?,
+            Error::ConflictWithoutTarget { .. } => f.write_str("ON CONFLICT requires conflict columns")?,
+            Error::ConstraintWithWhere { .. } => f.write_str("ON CONFLICT ON CONSTRAINT cannot have WHERE")?,
+            Error::DistinctOnMismatch { .. } => f.write_str("DISTINCT ON expressions must match the leading ORDER BY expressions")?,
+            Error::CompoundColumnCount { member, expected, given, .. } => {
+                write!(f, "select {} of the compound has {} columns but the first select has {}", member, given, expected)?
+            },
+            Error::InvalidOrder { .. } => f.write_str("order direction has to be ASC or DESC, optionally followed by NULLS FIRST or NULLS LAST")?,
+            Error::InvalidIdentifier { .. } => f.write_str("identifier has to be plain or double quoted names separated by .")?,
+            Error::InvalidCursor { .. } => f.write_str("cursor is not valid")?,
+            Error::LockNotAllowed { .. } => f.write_str("row locking is not allowed with DISTINCT, GROUP BY, HAVING, WINDOW or a compound select")?,
+        }
+        write!(f, "{}", self.context())
+    }
+}
+
+impl std::error::Error for Error {}
+
+// for TryInto bounds that also accept infallible conversions
+impl From<std::convert::Infallible> for Error {
+    fn from(e: std::convert::Infallible) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_error_display() {
+        let error = Error::EmptySet {
+            context: ErrorContext::identifier("user"),
+        }.within(BuilderKind::Update, Clause::Set);
+        assert_eq!(error.to_string(), "statement requires at least 1 set [builder: UPDATE, clause: SET, identifier: user]");
+        let error = Error::ParameterOverflow {
+            max: 65535,
+            context: ErrorContext::default(),
+        };
+        assert_eq!(error.to_string(), "statement has more than 65535 parameters");
+        let boxed: Box<dyn std::error::Error> = Box::new(error);
+        assert_eq!(boxed.to_string(), "statement has more than 65535 parameters");
+    }
+
+    #[test]
+    fn test_error_within_keeps_inner_context() {
+        let error = Error::JoinWithoutOn {
+            context: ErrorContext::clause(Clause::Join),
+        }.within(BuilderKind::Select, Clause::From).within(BuilderKind::Update, Clause::Where);
+        assert_eq!(error.context().builder, Some(BuilderKind::Select));
+        assert_eq!(error.context().clause, Some(Clause::Join));
+    }
 }
@@ -2,4 +2,4 @@
 mod error;
 pub mod postgres;
 pub use crate::postgres::PostgresBuilder;
-pub use crate::error::Error;
+pub use crate::error::{Error, ErrorContext, BuilderKind, Clause};
@@ -0,0 +1,284 @@
+use std::convert::TryInto;
+use crate::error::{Error, ErrorContext, BuilderKind, Clause};
+use crate::postgres::query_limit_offset::{QueryLimitOffset, LimitOffset};
+use crate::postgres::query_order::{QueryOrders, QueryOrder, OrderWay};
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParameterValue, ParamIter};
+use crate::postgres::expr::Expr;
+use crate::postgres::query_token::{QueryTokens, QueryToken};
+use crate::postgres::query_subquery::SubQuery;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompoundOperator {
+    Union,
+    UnionAll,
+    Intersect,
+    IntersectAll,
+    Except,
+    ExceptAll,
+}
+
+impl CompoundOperator {
+    fn keyword(self) -> &'static str {
+        match self {
+            CompoundOperator::Union => "UNION",
+            CompoundOperator::UnionAll => "UNION ALL",
+            CompoundOperator::Intersect => "INTERSECT",
+            CompoundOperator::IntersectAll => "INTERSECT ALL",
+            CompoundOperator::Except => "EXCEPT",
+            CompoundOperator::ExceptAll => "EXCEPT ALL",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CompoundMember {
+    operator: CompoundOperator, // not used by the first member
+    query: QueryTokens,
+    columns: Option<usize>, // None when it cannot be known before running, such as * or a built query
+}
+
+/// selects combined by UNION / INTERSECT / EXCEPT, each select is wrapped in parentheses
+/// and the parameters are numbered across all of them
+#[derive(Clone, Default, Debug)]
+pub struct SqlCompound<'a> {
+    _members: Vec<CompoundMember>,
+    _orders: QueryOrders,
+    _limit_offset: Option<QueryLimitOffset>,
+    _parameters: Vec<ParameterValue<'a>>,
+}
+
+impl<'a> SqlCompound<'a> {
+    pub fn new() -> SqlCompound<'static> {
+        SqlCompound::default()
+    }
+    pub fn build(&self) -> Result<QueryWithParams<'a>, Error> {
+        let mut param_iter = ParamIter::default();
+        let built = self.build_loc(&mut param_iter)?;
+        Ok(QueryWithParams {
+            query: built.query,
+            parameters: built.parameters_loc.into_iter().map(|ploc| self._parameters[ploc].clone()).collect(),
+        })
+    }
+    pub(crate) fn build_loc(&self, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let at = |clause: Clause| move |e: Error| e.within(BuilderKind::Compound, clause);
+        let mut vec: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        if self._members.is_empty() {
+            return Err(Error::Syntax {
+                message: "compound select requires at least 1 select".to_owned(),
+                context: ErrorContext::default(),
+            }).map_err(at(Clause::Select));
+        }
+        let expected = self.column_count();
+        for (index, member) in self._members.iter().enumerate() {
+            if let (Some(expected), Some(given)) = (expected, member.columns) {
+                if expected != given {
+                    return Err(Error::CompoundColumnCount {
+                        member: index,
+                        expected,
+                        given,
+                        context: ErrorContext::default(),
+                    }).map_err(at(Clause::Select));
+                }
+            }
+            if index > 0 {
+                vec.push(member.operator.keyword().to_owned());
+            }
+            let built = member.query.build(param_iter).map_err(at(Clause::Select))?;
+            vec.push(format!("({})", built.query));
+            p.extend(built.parameters_loc);
+        }
+        if self._orders.len() > 0 {
+            let built_for_order = self._orders.build(param_iter).map_err(at(Clause::OrderBy))?;
+            vec.push(format!("ORDER BY {}", built_for_order.query));
+            p.extend(built_for_order.parameters_loc);
+        }
+        if let Some(limitoffset) = &self._limit_offset {
+            if limitoffset.with_ties() && self._orders.len() == 0 {
+                return Err(Error::Syntax {
+                    message: "WITH TIES requires ORDER BY".to_owned(),
+                    context: ErrorContext::default(),
+                }).map_err(at(Clause::Limit));
+            }
+            let built_for_limit = limitoffset.build(param_iter).map_err(at(Clause::Limit))?;
+            vec.push(built_for_limit.query);
+            p.extend(built_for_limit.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: vec.join(" "),
+            parameters_loc: p,
+        })
+    }
+    pub(crate) fn into_parts(self) -> (SqlCompound<'static>, Vec<ParameterValue<'a>>) {
+        let compound = SqlCompound {
+            _members: self._members,
+            _orders: self._orders,
+            _limit_offset: self._limit_offset,
+            _parameters: Vec::new(),
+        };
+        (compound, self._parameters)
+    }
+    /// the column count of the first member that is known
+    pub(crate) fn column_count(&self) -> Option<usize> {
+        self._members.iter().find_map(|member| member.columns)
+    }
+    fn push_member(&mut self, operator: CompoundOperator, sub: SubQuery<'a>) -> &mut Self {
+        let columns = match &sub {
+            SubQuery::Select(select) => select.column_count(),
+            SubQuery::Compound(compound) => compound.column_count(),
+            _ => None,
+        };
+        let query = match sub {
+            SubQuery::Insert(_) | SubQuery::Update(_) | SubQuery::Delete(_) => QueryTokens(vec![QueryToken::Error(Error::Syntax {
+                message: "compound member has to be a select".to_owned(),
+                context: ErrorContext::default(),
+            })]),
+            SubQuery::Select(select) if select.has_lock() => QueryTokens(vec![QueryToken::Error(Error::LockNotAllowed {
+                context: ErrorContext {
+                    builder: None,
+                    clause: Some(Clause::Lock),
+                    identifier: Some(operator.keyword().to_owned()),
+                },
+            })]),
+            sub => {
+                let len = self._parameters.len();
+                let (tokens, parameters) = sub.into_format_query();
+                self._parameters.extend(parameters);
+                tokens.into_query_tokens(len)
+            },
+        };
+        self._members.push(CompoundMember {
+            operator,
+            query,
+            columns,
+        });
+        self
+    }
+    /// the operator of the first member is not used, so the first select can be given by any of them
+    pub fn union<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::Union, sub.into())
+    }
+    pub fn union_all<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::UnionAll, sub.into())
+    }
+    pub fn intersect<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::Intersect, sub.into())
+    }
+    pub fn intersect_all<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::IntersectAll, sub.into())
+    }
+    pub fn except<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::Except, sub.into())
+    }
+    pub fn except_all<S: Into<SubQuery<'a>>>(&mut self, sub: S) -> &mut Self {
+        self.push_member(CompoundOperator::ExceptAll, sub.into())
+    }
+    /// the order applies to the combined rows, so it can only use the output column names or positions
+    pub fn order<E: Into<Expr<'a>>, O>(&mut self, field: E, order_way: O) -> &mut Self where O: TryInto<OrderWay>, O::Error: Into<Error> {
+        let ftup = field.into().into_format_query();
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        self._orders.push(QueryOrder::new((ftup.0).into_query_tokens(len), order_way));
+        self
+    }
+    /// a usize, (limit, offset), or LimitOffset for bind parameters, LIMIT ALL, OFFSET only and FETCH FIRST
+    pub fn limit_offset<S: Into<LimitOffset<'a>>>(&mut self, limit_offset: S) -> &mut Self {
+        self._limit_offset = Some(limit_offset.into().into_query(&mut self._parameters));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::op;
+    use crate::postgres::select_builder::SqlSelect;
+    use crate::postgres::insert_builder::SqlInsert;
+    fn select<'a>(table: &str, fields: &[&str], id: &'a i32) -> SqlSelect<'a> {
+        let mut sqlselect = SqlSelect::new();
+        for field in fields {
+            sqlselect.select(*field);
+        }
+        sqlselect.table(table).and_where(op::gt("id", id));
+        sqlselect
+    }
+    #[test]
+    fn test_compound_builder() {
+        let mut sqlcompound = SqlCompound::new();
+        let qbuild = sqlcompound
+            .union(select("user", &["id", "name"], &1))
+            .union_all(select("admin", &["id", "name"], &2))
+            .except(select("banned", &["id", "name"], &3))
+            .order("name", "ASC")
+            .limit_offset((10, 20))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "(SELECT id, name FROM \"user\" WHERE id > $1) UNION ALL (SELECT id, name FROM \"admin\" WHERE id > $2) EXCEPT (SELECT id, name FROM \"banned\" WHERE id > $3) ORDER BY name ASC LIMIT 10 OFFSET 20");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn test_compound_builder_nested() {
+        let mut inner = SqlCompound::new();
+        inner
+            .union(select("user", &["id"], &1))
+            .union(select("admin", &["id"], &2));
+        let mut sqlcompound = SqlCompound::new();
+        let qbuild = sqlcompound
+            .intersect(inner)
+            .intersect(select("active", &["*"], &3))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "((SELECT id FROM \"user\" WHERE id > $1) UNION (SELECT id FROM \"admin\" WHERE id > $2)) INTERSECT (SELECT * FROM \"active\" WHERE id > $3)");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 2, 3]");
+
+        // the compound as a subquery of a select
+        let mut inner = SqlCompound::new();
+        inner
+            .union(select("user", &["id"], &1))
+            .union(select("admin", &["id"], &2));
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("COUNT(*)")
+            .table_subquery(inner, "u")
+            .and_where(op::lt("u.id", &4))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT COUNT(*) FROM ((SELECT id FROM \"user\" WHERE id > $1) UNION (SELECT id FROM \"admin\" WHERE id > $2)) AS u WHERE u.id < $3");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 2, 4]");
+    }
+
+    #[test]
+    fn test_compound_builder_error() {
+        let mut sqlcompound = SqlCompound::new();
+        let error = sqlcompound
+            .union(select("user", &["id", "name"], &1))
+            .union(select("admin", &["id"], &2))
+            .build().unwrap_err();
+        assert_eq!(error, Error::CompoundColumnCount {
+            member: 1,
+            expected: 2,
+            given: 1,
+            context: ErrorContext {
+                builder: Some(BuilderKind::Compound),
+                clause: Some(Clause::Select),
+                identifier: None,
+            },
+        });
+        assert!(matches!(SqlCompound::new().build(), Err(Error::Syntax { .. })));
+        let mut sqlinsert = SqlInsert::new();
+        sqlinsert.table("user").set("id", &1);
+        let mut sqlcompound = SqlCompound::new();
+        let error = sqlcompound
+            .union(select("user", &["id"], &1))
+            .union(sqlinsert)
+            .build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        let mut locked = select("job", &["id"], &1);
+        locked.lock(crate::postgres::query_lock::Lock::update());
+        let mut sqlcompound = SqlCompound::new();
+        let error = sqlcompound
+            .union(select("user", &["id"], &1))
+            .union_all(locked)
+            .build().unwrap_err();
+        assert!(matches!(error, Error::LockNotAllowed { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("UNION ALL"));
+    }
+}
@@ -0,0 +1,106 @@
+//! boolean condition tree, built apart from the statement and given to and_where / or_where / join on
+use crate::postgres::expr::{Expr, BinaryOp, UnaryOp};
+use crate::postgres::query_token::{FormatQueryTup};
+
+#[derive(Debug)]
+pub enum Condition<'a> {
+    /// all of the conditions, an empty list is TRUE
+    And(Vec<Condition<'a>>),
+    /// any of the conditions, an empty list is FALSE
+    Or(Vec<Condition<'a>>),
+    Not(Box<Condition<'a>>),
+    Expr(Expr<'a>),
+}
+
+pub fn and<'a, C: Into<Condition<'a>>>(conditions: Vec<C>) -> Condition<'a> {
+    Condition::And(conditions.into_iter().map(|c| c.into()).collect())
+}
+pub fn or<'a, C: Into<Condition<'a>>>(conditions: Vec<C>) -> Condition<'a> {
+    Condition::Or(conditions.into_iter().map(|c| c.into()).collect())
+}
+pub fn not<'a, C: Into<Condition<'a>>>(condition: C) -> Condition<'a> {
+    Condition::Not(Box::new(condition.into()))
+}
+
+impl<'a> Condition<'a> {
+    fn join(conditions: Vec<Condition<'a>>, op: BinaryOp, empty: &str) -> Expr<'a> {
+        conditions.into_iter()
+            .map(|c| c.into_expr())
+            .fold(None, |acc: Option<Expr<'a>>, expr| match acc {
+                Some(acc) => Some(Expr::Binary(Box::new(acc), op.clone(), Box::new(expr))),
+                None => Some(expr),
+            })
+            .unwrap_or_else(|| Expr::from(empty))
+    }
+    /// the parentheses come from the operator precedence of Expr
+    pub fn into_expr(self) -> Expr<'a> {
+        match self {
+            Condition::And(conditions) => Condition::join(conditions, BinaryOp::And, "TRUE"),
+            Condition::Or(conditions) => Condition::join(conditions, BinaryOp::Or, "FALSE"),
+            Condition::Not(condition) => Expr::Unary(UnaryOp::Not, Box::new(condition.into_expr())),
+            Condition::Expr(expr) => expr,
+        }
+    }
+}
+
+impl<'a> From<Expr<'a>> for Condition<'a> {
+    fn from(expr: Expr<'a>) -> Self {
+        Condition::Expr(expr)
+    }
+}
+impl<'a> From<FormatQueryTup<'a>> for Condition<'a> {
+    fn from(ftup: FormatQueryTup<'a>) -> Self {
+        Condition::Expr(ftup.into())
+    }
+}
+impl<'a> From<&str> for Condition<'a> {
+    fn from(sql: &str) -> Self {
+        Condition::Expr(sql.into())
+    }
+}
+impl<'a> From<String> for Condition<'a> {
+    fn from(sql: String) -> Self {
+        Condition::Expr(sql.into())
+    }
+}
+impl<'a> From<Condition<'a>> for Expr<'a> {
+    fn from(condition: Condition<'a>) -> Self {
+        condition.into_expr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::op;
+    use crate::postgres::expr::{col, bind};
+    use crate::postgres::query_field::ParamIter;
+    fn build(condition: Condition) -> (String, usize) {
+        let (tokens, parameters) = condition.into_expr().into_format_query();
+        let built = tokens.into_query_tokens(0).build(&mut ParamIter::default()).unwrap();
+        assert_eq!(built.parameters_loc.len(), parameters.len());
+        (built.query, parameters.len())
+    }
+    #[test]
+    fn test_condition_nested() {
+        let condition = and(vec![
+            or(vec![op::eq("a", &1), op::eq("b", &2)]),
+            not(op::eq("c", &3)),
+            and(vec![col("d").gt(bind(&4)), col("e").lt(bind(&5))]),
+        ]);
+        assert_eq!(build(condition), ("(a = $1 OR b = $2) AND NOT (c = $3) AND (\"d\" > $4 AND \"e\" < $5)".to_owned(), 5));
+        let condition = or(vec![
+            and(vec![op::eq("a", &1), op::eq("b", &2)]),
+            Condition::from("c IS NULL"),
+        ]);
+        assert_eq!(build(condition).0, "(a = $1) AND (b = $2) OR c IS NULL");
+        assert_eq!(build(not(or(vec![col("a").eq(bind(&1)), col("b").eq(bind(&2))]))).0, "NOT (\"a\" = $1 OR \"b\" = $2)");
+    }
+    #[test]
+    fn test_condition_empty_and_single() {
+        let empty: Vec<Condition> = Vec::new();
+        assert_eq!(build(and(vec![or(empty)])).0, "FALSE");
+        assert_eq!(build(and(Vec::<Condition>::new())).0, "TRUE");
+        assert_eq!(build(and(vec![op::eq("a", &1)])).0, "a = $1");
+    }
+}
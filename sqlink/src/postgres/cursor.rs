@@ -0,0 +1,92 @@
+use std::str::FromStr;
+use crate::error::{Error, ErrorContext};
+
+/// opaque page token for SqlSelect::seek_after, holding the key values of the last row as text
+///
+/// the values are read back with the type of the column, so the parameter has the same type as before
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cursor(Vec<String>);
+
+fn invalid(token: &str) -> Error {
+    Error::InvalidCursor { context: ErrorContext::identifier(token) }
+}
+
+impl Cursor {
+    pub fn new() -> Self {
+        Cursor::default()
+    }
+    /// the values are pushed in the same order as the seek_after keys
+    pub fn push<T: ToString>(mut self, value: &T) -> Self {
+        self.0.push(value.to_string());
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// the value at idx parsed as T, a missing value or one that cannot be parsed is InvalidCursor
+    pub fn get<T: FromStr>(&self, idx: usize) -> Result<T, Error> {
+        match self.0.get(idx) {
+            Some(value) => value.parse().map_err(|_| invalid(value)),
+            None => Err(invalid(&self.encode())),
+        }
+    }
+    /// each value is written as {length}:{value}, then hex encoded so it is safe in a url
+    pub fn encode(&self) -> String {
+        let raw: String = self.0.iter().map(|value| format!("{}:{}", value.len(), value)).collect();
+        raw.bytes().map(|b| format!("{:02x}", b)).collect()
+    }
+    // is_multiple_of is too new for the rust this crate builds with
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn decode(token: &str) -> Result<Self, Error> {
+        if token.len() % 2 != 0 || !token.is_ascii() {
+            return Err(invalid(token));
+        }
+        let bytes = (0..token.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid(token))?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid(token))?;
+        let mut values: Vec<String> = Vec::new();
+        let mut rest = raw.as_str();
+        while !rest.is_empty() {
+            let colon = rest.find(':').ok_or_else(|| invalid(token))?;
+            let len: usize = rest[..colon].parse().map_err(|_| invalid(token))?;
+            let end = (colon + 1).checked_add(len).ok_or_else(|| invalid(token))?;
+            let value = rest.get(colon + 1..end).ok_or_else(|| invalid(token))?;
+            values.push(value.to_owned());
+            rest = &rest[end..];
+        }
+        Ok(Cursor(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor::new().push(&"2020-01-02 03:04:05:06").push(&42).push(&"");
+        let token = cursor.encode();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        let decoded = Cursor::decode(&token).unwrap();
+        assert_eq!(decoded, cursor);
+        assert_eq!(decoded.get::<String>(0).unwrap(), "2020-01-02 03:04:05:06");
+        assert_eq!(decoded.get::<i32>(1).unwrap(), 42);
+        assert_eq!(decoded.get::<String>(2).unwrap(), "");
+        assert!(matches!(decoded.get::<i32>(0), Err(Error::InvalidCursor { .. })));
+        assert!(matches!(decoded.get::<i32>(3), Err(Error::InvalidCursor { .. })));
+        assert_eq!(Cursor::decode("").unwrap(), Cursor::new());
+    }
+    #[test]
+    fn test_cursor_invalid() {
+        // the last one is "18446744073709551615:", a length that overflows usize
+        for token in &["abc", "zz", "3a", "333a3132", "ff", "é1", "31383434363734343037333730393535313631353a"] {
+            let error = Cursor::decode(token).unwrap_err();
+            assert!(matches!(error, Error::InvalidCursor { .. }), "{}", token);
+            assert_eq!(error.context().identifier.as_deref(), Some(*token));
+        }
+    }
+}
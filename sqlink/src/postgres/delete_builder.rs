@@ -1,66 +1,108 @@
-use crate::error::Error;
-use crate::postgres::query_table::{QueryTables, QueryTable};
-use crate::postgres::query_field::{QueryWithParams, ParameterValueAsRef};
-use crate::postgres::query_token::{FormatQueryTup};
+use crate::error::{Error, BuilderKind, Clause};
+use crate::postgres::query_table::{QueryTables, QueryTable, QueryFroms, TableJoin};
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParameterValue, ParamIter};
+use crate::postgres::expr::Expr;
 use crate::postgres::query_where::{QueryWheres, WhereOperator};
+use crate::postgres::query_return::QueryReturns;
+use crate::postgres::query_with::{QueryWiths, WithQuery};
+use crate::postgres::ident::Alias;
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct SqlDelete<'a> {
+    _withs: QueryWiths,
     _tables: QueryTables,
+    _froms: QueryFroms,
     _wheres: QueryWheres,
-    _parameters: Vec<ParameterValueAsRef<'a>>,
+    _returns: QueryReturns,
+    _parameters: Vec<ParameterValue<'a>>,
 }
 
 impl<'a> SqlDelete<'a> {
     pub fn new() -> SqlDelete<'static> {
         SqlDelete::default()
     }
-    pub fn build(&self) -> Result<QueryWithParams, Error> {
-        if self._tables.len() != 1 {
-            return Err(Error::Syntax("currently only support 1 table for DELETE".to_owned()))
-        }
-        let mut param_iter = 1;
-        let built_for_table = self._tables.build(&mut param_iter)?;
+    pub fn build(&self) -> Result<QueryWithParams<'a>, Error> {
+        let mut param_iter = ParamIter::default();
+        let built = self.build_loc(&mut param_iter)?;
+        Ok(QueryWithParams {
+            query: built.query,
+            parameters: built.parameters_loc.into_iter().map(|ploc| self._parameters[ploc].clone()).collect(),
+        })
+    }
+    pub(crate) fn build_loc(&self, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let at = |clause: Clause| move |e: Error| e.within(BuilderKind::Delete, clause);
         let mut vec: Vec<String> = Vec::new();
-        let mut p: Vec<ParameterValueAsRef> = Vec::new();
-        for ploc in built_for_table.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let mut p: Vec<usize> = Vec::new();
+        if self._withs.len() > 0 {
+            let built_for_with = self._withs.build(param_iter).map_err(at(Clause::With))?;
+            vec.push(built_for_with.query);
+            p.extend(built_for_with.parameters_loc);
         }
+        let built_for_table = self._tables.build(param_iter).map_err(at(Clause::From))?;
+        p.extend(built_for_table.parameters_loc);
         vec.push(format!("DELETE FROM {}", built_for_table.query));
-        if self._wheres.len() > 0 {
-            let built_for_where = self._wheres.build(&mut param_iter)?;
+        if !self._froms.is_empty() {
+            let built_for_from = self._froms.build(param_iter).map_err(at(Clause::From))?;
+            vec.push(format!("USING {}", built_for_from.query));
+            p.extend(built_for_from.parameters_loc);
+        }
+        let built_for_where = self._froms.build_wheres(&self._wheres, param_iter).map_err(at(Clause::Where))?;
+        if !built_for_where.query.is_empty() {
             vec.push(format!("WHERE {}", built_for_where.query));
-            for ploc in built_for_where.parameters_loc {
-                p.push(self._parameters[ploc]);
-            }
+            p.extend(built_for_where.parameters_loc);
         }
-
-        Ok(QueryWithParams {
+        if self._returns.len() > 0 {
+            let built_for_return = self._returns.build(param_iter).map_err(at(Clause::Returning))?;
+            vec.push(format!("RETURNING {}", built_for_return.query));
+            p.extend(built_for_return.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
             query: vec.join(" "),
-            parameters: p,
+            parameters_loc: p,
         })
     }
+    pub(crate) fn into_parts(self) -> (SqlDelete<'static>, Vec<ParameterValue<'a>>) {
+        let delete = SqlDelete {
+            _withs: self._withs,
+            _tables: self._tables,
+            _froms: self._froms,
+            _wheres: self._wheres,
+            _returns: self._returns,
+            _parameters: Vec::new(),
+        };
+        (delete, self._parameters)
+    }
+    pub fn returning<E: Into<Expr<'a>>>(&mut self, field: E) -> &mut Self {
+        self._returns.push_expr(field.into(), None, &mut self._parameters);
+        self
+    }
+    pub fn returning_as<E: Into<Expr<'a>>, T: Into<Alias>>(&mut self, field: E, alias: T) -> &mut Self {
+        self._returns.push_expr(field.into(), Some(alias.into().to_sql()), &mut self._parameters);
+        self
+    }
     pub fn table<S: Into<QueryTable>>(&mut self, table: S) -> &mut Self {
         self._tables.push(table.into());
         self
     }
-    pub fn and_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn and_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::And);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
-    pub fn or_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn or_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::Or);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
@@ -80,46 +122,59 @@ impl<'a> SqlDelete<'a> {
     }
     pub fn where_close(&mut self) -> &mut Self {
         self._wheres.push(WhereOperator::Close);
-        // lets dont panic, if error database will panic
         self
     }
-    pub fn inner_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    /// USING "table", the other tables that the where can refer to
+    pub fn using<S: Into<QueryTable>>(&mut self, table: S) -> &mut Self {
+        self._froms.push(table.into());
+        self
+    }
+    /// USING the table with the ON condition in WHERE, or joined to the last table of using()
+    pub fn inner_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.inner_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::InnerJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn left_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    /// outer joins can only join the tables of using(), the build fails when there is none
+    pub fn left_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.left_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::LeftJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn right_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn right_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.right_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::RightJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn full_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn full_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.full_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::FullJoin(query_table.into(), qtokens.into()));
         self
     }
 }
 
+impl<'a> WithQuery<'a> for SqlDelete<'a> {
+    fn withs_mut(&mut self) -> (&mut QueryWiths, &mut Vec<ParameterValue<'a>>) {
+        (&mut self._withs, &mut self._parameters)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::postgres::query_token::{format_query};
+    use crate::postgres::select_builder::SqlSelect;
     use super::*;
     #[test]
     fn test_update_builder_1() {
@@ -130,4 +185,67 @@ mod tests {
             .build().unwrap();
         assert_eq!(qbuild.query, "DELETE FROM \"user\" WHERE id = $1");
     }
+
+    #[test]
+    fn test_delete_builder_returning() {
+        let mut sql_select = SqlSelect::new();
+        sql_select
+            .select("user_id")
+            .table("ban")
+            .and_where(format_query("level > {}", vec![&2]));
+        let mut sql_delete = SqlDelete::new();
+        let qbuild = sql_delete
+            .with("banned", sql_select)
+            .table("user")
+            .and_where(format_query("id IN (SELECT user_id FROM banned) AND age > {}", vec![&(18)]))
+            .returning("id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH banned AS (SELECT user_id FROM \"ban\" WHERE level > $1) DELETE FROM \"user\" WHERE id IN (SELECT user_id FROM banned) AND age > $2 RETURNING id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[2, 18]");
+    }
+
+    #[test]
+    fn test_delete_builder_using() {
+        let mut sql_delete = SqlDelete::new();
+        let qbuild = sql_delete
+            .table(("session", "s"))
+            .using(("user", "u"))
+            .and_where(format_query("s.user_id = u.id AND u.banned = {}", vec![&true]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "DELETE FROM \"session\" AS s USING \"user\" AS u WHERE s.user_id = u.id AND u.banned = $1");
+
+        let mut sql_delete = SqlDelete::new();
+        let qbuild = sql_delete
+            .table(("session", "s"))
+            .inner_join(("user", "u"), format_query("u.id = s.user_id", vec![]))
+            .and_where(format_query("u.banned = {}", vec![&true]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "DELETE FROM \"session\" AS s USING \"user\" AS u WHERE (u.id = s.user_id) AND (u.banned = $1)");
+
+        let mut sql_delete = SqlDelete::new();
+        let qbuild = sql_delete
+            .table(("session", "s"))
+            .inner_join(("user", "u"), format_query("u.id = s.user_id", vec![]))
+            .inner_join(("device", "d"), format_query("d.id = s.device_id", vec![]))
+            .and_where(format_query("u.banned = {} OR d.lost = {}", vec![&true, &true]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "DELETE FROM \"session\" AS s USING \"user\" AS u,\"device\" AS d WHERE (u.id = s.user_id) AND (d.id = s.device_id) AND (u.banned = $1 OR d.lost = $2)");
+
+        // an OR in the ON stays inside its parentheses
+        let mut sql_delete = SqlDelete::new();
+        let qbuild = sql_delete
+            .table(("session", "s"))
+            .inner_join(("user", "u"), format_query("u.id = s.user_id OR u.email = s.email", vec![]))
+            .and_where(format_query("u.banned = {}", vec![&true]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "DELETE FROM \"session\" AS s USING \"user\" AS u WHERE (u.id = s.user_id OR u.email = s.email) AND (u.banned = $1)");
+
+        let mut sql_delete = SqlDelete::new();
+        let error = sql_delete
+            .table("session")
+            .full_join("user", format_query("user.id = session.user_id", vec![]))
+            .build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.context().builder, Some(BuilderKind::Delete));
+    }
 }
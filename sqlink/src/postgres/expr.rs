@@ -0,0 +1,775 @@
+//! typed expression, lowered into the same tokens as format_query
+//!
+//! a plain `&str` or `String` is a raw sql fragment, same as everywhere else in the builders,
+//! use `col` for an escaped column, `bind` for a parameter and `lit` for an inline literal
+use std::convert::TryInto;
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::ParameterValue;
+use crate::postgres::query_order::OrderWay;
+use crate::postgres::query_token::{TmpQueryTokens, TmpQueryToken, FormatQueryTup};
+use crate::postgres::ident::{Ident, Alias};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Literal {
+    fn to_sql(&self) -> String {
+        match self {
+            Literal::Null => "NULL".to_owned(),
+            Literal::Bool(true) => "TRUE".to_owned(),
+            Literal::Bool(false) => "FALSE".to_owned(),
+            Literal::Int(i) => i.to_string(),
+            Literal::Float(f) if f.is_finite() => f.to_string(),
+            Literal::Float(f) => format!("'{}'::float8", f),
+            Literal::Str(s) => format!("'{}'", s.replace('\'', "''")),
+        }
+    }
+    fn is_negative(&self) -> bool {
+        match self {
+            Literal::Int(i) => *i < 0,
+            Literal::Float(f) => f.is_finite() && *f < 0.0,
+            _ => false,
+        }
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Self {
+        Literal::Bool(b)
+    }
+}
+impl From<i16> for Literal {
+    fn from(i: i16) -> Self {
+        Literal::Int(i.into())
+    }
+}
+impl From<i32> for Literal {
+    fn from(i: i32) -> Self {
+        Literal::Int(i.into())
+    }
+}
+impl From<i64> for Literal {
+    fn from(i: i64) -> Self {
+        Literal::Int(i)
+    }
+}
+impl From<f32> for Literal {
+    fn from(f: f32) -> Self {
+        Literal::Float(f.into())
+    }
+}
+impl From<f64> for Literal {
+    fn from(f: f64) -> Self {
+        Literal::Float(f)
+    }
+}
+impl From<&str> for Literal {
+    fn from(s: &str) -> Self {
+        Literal::Str(s.to_owned())
+    }
+}
+impl From<String> for Literal {
+    fn from(s: String) -> Self {
+        Literal::Str(s)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+    Like,
+    ILike,
+    /// any other operator, such as @> or &&
+    Custom(String),
+}
+
+impl BinaryOp {
+    fn to_sql(&self) -> &str {
+        match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Lte => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Gte => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Concat => "||",
+            BinaryOp::Like => "LIKE",
+            BinaryOp::ILike => "ILIKE",
+            BinaryOp::Custom(op) => op,
+        }
+    }
+    // postgres operator precedence, higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Lte | BinaryOp::Gt | BinaryOp::Gte => 5,
+            BinaryOp::Like | BinaryOp::ILike => 6,
+            BinaryOp::Concat | BinaryOp::Custom(_) => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 9,
+        }
+    }
+    // comparison cannot be chained, so same precedence on the left also needs parentheses
+    fn is_left_assoc(&self) -> bool {
+        !matches!(self.precedence(), 5 | 6)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+    IsNull,
+    IsNotNull,
+}
+
+impl UnaryOp {
+    fn precedence(&self) -> u8 {
+        match self {
+            UnaryOp::Not => 3,
+            UnaryOp::IsNull | UnaryOp::IsNotNull => 4,
+            UnaryOp::Neg => 10,
+        }
+    }
+}
+
+/// CASE [operand] WHEN ... THEN ... [ELSE ...] END
+#[derive(Debug)]
+pub struct Case<'a> {
+    operand: Option<Box<Expr<'a>>>,
+    whens: Vec<(Expr<'a>, Expr<'a>)>,
+    otherwise: Option<Box<Expr<'a>>>,
+}
+
+impl<'a> Case<'a> {
+    pub fn when<S: Into<Expr<'a>>, T: Into<Expr<'a>>>(mut self, condition: S, then: T) -> Self {
+        self.whens.push((condition.into(), then.into()));
+        self
+    }
+    pub fn otherwise<S: Into<Expr<'a>>>(mut self, expr: S) -> Self {
+        self.otherwise = Some(Box::new(expr.into()));
+        self
+    }
+    pub fn end(self) -> Expr<'a> {
+        Expr::Case(self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameUnit {
+    Rows,
+    Range,
+    Groups,
+}
+
+/// start or end of a window frame, the offset can be any expression such as bind(&3) or '1 day'::interval
+#[derive(Debug)]
+pub enum FrameBound<'a> {
+    UnboundedPreceding,
+    Preceding(Expr<'a>),
+    CurrentRow,
+    Following(Expr<'a>),
+    UnboundedFollowing,
+}
+
+impl<'a> FrameBound<'a> {
+    // a frame cannot end before it starts
+    fn rank(&self) -> u8 {
+        match self {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(_) => 1,
+            FrameBound::CurrentRow => 2,
+            FrameBound::Following(_) => 3,
+            FrameBound::UnboundedFollowing => 4,
+        }
+    }
+}
+
+/// the window of OVER (...) or of the WINDOW clause
+#[derive(Debug, Default)]
+pub struct Window<'a> {
+    base: Option<String>,
+    partitions: Vec<Expr<'a>>,
+    orders: Vec<(Expr<'a>, Result<OrderWay, Error>)>,
+    frame: Option<(FrameUnit, FrameBound<'a>, Option<FrameBound<'a>>)>,
+}
+
+impl<'a> Window<'a> {
+    /// build on a named window of the WINDOW clause, the named window cannot have a frame
+    pub fn base<S: Into<Alias>>(mut self, name: S) -> Self {
+        self.base = Some(name.into().to_sql());
+        self
+    }
+    pub fn partition_by<S: Into<Expr<'a>>>(mut self, expr: S) -> Self {
+        self.partitions.push(expr.into());
+        self
+    }
+    pub fn order_by<S: Into<Expr<'a>>, O>(mut self, expr: S, order_way: O) -> Self where O: TryInto<OrderWay>, O::Error: Into<Error> {
+        self.orders.push((expr.into(), order_way.try_into().map_err(|e| e.into())));
+        self
+    }
+    /// unit BETWEEN start AND end, or only unit start when there is no end
+    pub fn frame(mut self, unit: FrameUnit, start: FrameBound<'a>, end: Option<FrameBound<'a>>) -> Self {
+        self.frame = Some((unit, start, end));
+        self
+    }
+    pub fn rows(self, start: FrameBound<'a>, end: FrameBound<'a>) -> Self {
+        self.frame(FrameUnit::Rows, start, Some(end))
+    }
+    pub fn range(self, start: FrameBound<'a>, end: FrameBound<'a>) -> Self {
+        self.frame(FrameUnit::Range, start, Some(end))
+    }
+    pub fn groups(self, start: FrameBound<'a>, end: FrameBound<'a>) -> Self {
+        self.frame(FrameUnit::Groups, start, Some(end))
+    }
+    /// the tokens inside the parentheses, for the WINDOW clause
+    pub fn into_format_query(self) -> FormatQueryTup<'a> {
+        let mut writer = ExprWriter::default();
+        writer.write_window(self);
+        (TmpQueryTokens(writer.tokens), writer.parameters)
+    }
+}
+
+#[derive(Debug)]
+pub enum Over<'a> {
+    /// OVER name, a window of the WINDOW clause
+    Named(String),
+    Window(Box<Window<'a>>),
+}
+
+#[derive(Debug)]
+pub enum Expr<'a> {
+    /// "table"."column", each part is escaped
+    Column(Ident),
+    Literal(Literal),
+    Bind(ParameterValue<'a>),
+    Function(String, Vec<Expr<'a>>),
+    Binary(Box<Expr<'a>>, BinaryOp, Box<Expr<'a>>),
+    Unary(UnaryOp, Box<Expr<'a>>),
+    Case(Case<'a>),
+    /// window function call, func("row_number", vec![]).over(...)
+    Over(Box<Expr<'a>>, Over<'a>),
+    Cast(Box<Expr<'a>>, String),
+    /// (a, b, c), a row value or a list for IN
+    Tuple(Vec<Expr<'a>>),
+    /// from format_query / compose_query or a plain string, written as is
+    Raw(TmpQueryTokens, Vec<ParameterValue<'a>>),
+}
+
+/// "name" or "table"."name", * is not escaped
+pub fn col<'a, S: Into<Ident>>(name: S) -> Expr<'a> {
+    Expr::Column(name.into())
+}
+pub fn lit<'a, S: Into<Literal>>(value: S) -> Expr<'a> {
+    Expr::Literal(value.into())
+}
+pub fn null<'a>() -> Expr<'a> {
+    Expr::Literal(Literal::Null)
+}
+pub fn bind<'a, P: Into<ParameterValue<'a>>>(param: P) -> Expr<'a> {
+    Expr::Bind(param.into())
+}
+pub fn func<'a, S: Into<String>>(name: S, args: Vec<Expr<'a>>) -> Expr<'a> {
+    Expr::Function(name.into(), args)
+}
+pub fn cast<'a, S: Into<Expr<'a>>, T: Into<String>>(expr: S, type_name: T) -> Expr<'a> {
+    Expr::Cast(Box::new(expr.into()), type_name.into())
+}
+pub fn not<'a, S: Into<Expr<'a>>>(expr: S) -> Expr<'a> {
+    Expr::Unary(UnaryOp::Not, Box::new(expr.into()))
+}
+pub fn neg<'a, S: Into<Expr<'a>>>(expr: S) -> Expr<'a> {
+    Expr::Unary(UnaryOp::Neg, Box::new(expr.into()))
+}
+pub fn tuple<'a>(exprs: Vec<Expr<'a>>) -> Expr<'a> {
+    Expr::Tuple(exprs)
+}
+pub fn window<'a>() -> Window<'a> {
+    Window::default()
+}
+/// searched CASE WHEN condition THEN ...
+pub fn case<'a>() -> Case<'a> {
+    Case {
+        operand: None,
+        whens: Vec::new(),
+        otherwise: None,
+    }
+}
+/// simple CASE operand WHEN value THEN ...
+pub fn case_of<'a, S: Into<Expr<'a>>>(operand: S) -> Case<'a> {
+    Case {
+        operand: Some(Box::new(operand.into())),
+        whens: Vec::new(),
+        otherwise: None,
+    }
+}
+
+impl<'a> Expr<'a> {
+    fn binary<S: Into<Expr<'a>>>(self, op: BinaryOp, rhs: S) -> Expr<'a> {
+        Expr::Binary(Box::new(self), op, Box::new(rhs.into()))
+    }
+    pub fn eq<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Eq, rhs)
+    }
+    pub fn ne<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Ne, rhs)
+    }
+    pub fn lt<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Lt, rhs)
+    }
+    pub fn lte<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Lte, rhs)
+    }
+    pub fn gt<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Gt, rhs)
+    }
+    pub fn gte<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Gte, rhs)
+    }
+    pub fn and<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::And, rhs)
+    }
+    pub fn or<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Or, rhs)
+    }
+    pub fn concat<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Concat, rhs)
+    }
+    pub fn like<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Like, rhs)
+    }
+    pub fn ilike<S: Into<Expr<'a>>>(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::ILike, rhs)
+    }
+    /// any other binary operator, such as @> or &&
+    pub fn op<S: Into<String>, T: Into<Expr<'a>>>(self, op: S, rhs: T) -> Expr<'a> {
+        self.binary(BinaryOp::Custom(op.into()), rhs)
+    }
+    pub fn is_null(self) -> Expr<'a> {
+        Expr::Unary(UnaryOp::IsNull, Box::new(self))
+    }
+    pub fn is_not_null(self) -> Expr<'a> {
+        Expr::Unary(UnaryOp::IsNotNull, Box::new(self))
+    }
+    pub fn cast<S: Into<String>>(self, type_name: S) -> Expr<'a> {
+        cast(self, type_name)
+    }
+    pub fn over(self, window: Window<'a>) -> Expr<'a> {
+        Expr::Over(Box::new(self), Over::Window(Box::new(window)))
+    }
+    pub fn over_named<S: Into<Alias>>(self, name: S) -> Expr<'a> {
+        Expr::Over(Box::new(self), Over::Named(name.into().to_sql()))
+    }
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(_, op, _) => op.precedence(),
+            Expr::Unary(op, _) => op.precedence(),
+            Expr::Literal(l) if l.is_negative() => UnaryOp::Neg.precedence(),
+            // a raw fragment can contain anything, so it binds as loose as OR unless it is a plain name
+            Expr::Raw(tokens, _) => match tokens.0.as_slice() {
+                [TmpQueryToken::Sql(sql)] if sql.chars().all(|c| c.is_alphanumeric() || "_.\"$".contains(c)) => u8::MAX,
+                _ => BinaryOp::Or.precedence(),
+            },
+            _ => u8::MAX,
+        }
+    }
+    /// lower into the tokens used by the builders, the same as format_query
+    pub fn into_format_query(self) -> FormatQueryTup<'a> {
+        let mut writer = ExprWriter::default();
+        writer.write(self, 0, false);
+        (TmpQueryTokens(writer.tokens), writer.parameters)
+    }
+    /// same as into_format_query, but OR is wrapped in parentheses so it can be joined with other conditions by AND
+    pub(crate) fn into_condition(self) -> FormatQueryTup<'a> {
+        let mut writer = ExprWriter::default();
+        // a raw condition is given as it is, the same as a where with format_query
+        let parent = if let Expr::Raw(..) = self { 0 } else { BinaryOp::And.precedence() };
+        writer.write(self, parent, false);
+        (TmpQueryTokens(writer.tokens), writer.parameters)
+    }
+}
+
+#[derive(Default)]
+struct ExprWriter<'a> {
+    tokens: Vec<TmpQueryToken>,
+    parameters: Vec<ParameterValue<'a>>,
+}
+
+impl<'a> ExprWriter<'a> {
+    fn push_sql(&mut self, sql: &str) {
+        if let Some(TmpQueryToken::Sql(last)) = self.tokens.last_mut() {
+            last.push_str(sql);
+        } else {
+            self.tokens.push(TmpQueryToken::Sql(sql.to_owned()));
+        }
+    }
+    fn write_list(&mut self, exprs: Vec<Expr<'a>>) {
+        for (idx, expr) in exprs.into_iter().enumerate() {
+            if idx > 0 {
+                self.push_sql(", ");
+            }
+            self.write(expr, 0, false);
+        }
+    }
+    fn push_error(&mut self, message: &str) {
+        self.tokens.push(TmpQueryToken::Error(Error::Syntax {
+            message: message.to_owned(),
+            context: ErrorContext::default(),
+        }));
+    }
+    fn write_frame_bound(&mut self, bound: FrameBound<'a>) {
+        match bound {
+            FrameBound::UnboundedPreceding => self.push_sql("UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(offset) => {
+                self.write_operand(offset);
+                self.push_sql(" PRECEDING");
+            },
+            FrameBound::CurrentRow => self.push_sql("CURRENT ROW"),
+            FrameBound::Following(offset) => {
+                self.write_operand(offset);
+                self.push_sql(" FOLLOWING");
+            },
+            FrameBound::UnboundedFollowing => self.push_sql("UNBOUNDED FOLLOWING"),
+        }
+    }
+    fn write_window(&mut self, window: Window<'a>) {
+        let mut started = false;
+        if let Some(base) = &window.base {
+            self.push_sql(base);
+            started = true;
+        }
+        if !window.partitions.is_empty() {
+            self.push_sql(if started { " PARTITION BY " } else { "PARTITION BY " });
+            self.write_list(window.partitions);
+            started = true;
+        }
+        if !window.orders.is_empty() {
+            self.push_sql(if started { " ORDER BY " } else { "ORDER BY " });
+            for (idx, (expr, order_way)) in window.orders.into_iter().enumerate() {
+                if idx > 0 {
+                    self.push_sql(", ");
+                }
+                self.write(expr, 0, false);
+                match order_way.map(|order_way| order_way.to_sql()) {
+                    Ok(order_way) if order_way.is_empty() => (),
+                    Ok(order_way) => self.push_sql(&format!(" {}", order_way)),
+                    Err(e) => self.tokens.push(TmpQueryToken::Error(e)),
+                }
+            }
+            started = true;
+        }
+        if let Some((unit, start, end)) = window.frame {
+            if started {
+                self.push_sql(" ");
+            }
+            self.push_sql(match unit {
+                FrameUnit::Rows => "ROWS ",
+                FrameUnit::Range => "RANGE ",
+                FrameUnit::Groups => "GROUPS ",
+            });
+            if let FrameBound::UnboundedFollowing = start {
+                self.push_error("window frame cannot start with UNBOUNDED FOLLOWING");
+            }
+            match end {
+                Some(end) => {
+                    if let FrameBound::UnboundedPreceding = end {
+                        self.push_error("window frame cannot end with UNBOUNDED PRECEDING");
+                    } else if start.rank() > end.rank() {
+                        self.push_error("window frame cannot end before it starts");
+                    }
+                    self.push_sql("BETWEEN ");
+                    self.write_frame_bound(start);
+                    self.push_sql(" AND ");
+                    self.write_frame_bound(end);
+                },
+                None => self.write_frame_bound(start),
+            }
+        }
+    }
+    // the operand of a keyword such as OVER or PRECEDING, an operator is always wrapped
+    // but a raw fragment is kept as it is, since count(*) OVER cannot be written as (count(*)) OVER
+    fn write_operand(&mut self, expr: Expr<'a>) {
+        let parent = if let Expr::Raw(..) = expr { 0 } else { u8::MAX };
+        self.write(expr, parent, false);
+    }
+    // wrap in parentheses when the parent operator binds tighter
+    fn write(&mut self, expr: Expr<'a>, parent: u8, wrap_same: bool) {
+        let precedence = expr.precedence();
+        // OR is associative, so a raw fragment is only wrapped below OR
+        let raw = matches!(expr, Expr::Raw(..));
+        let wrap = precedence < parent || (precedence == parent && wrap_same && !raw);
+        if wrap {
+            self.push_sql("(");
+        }
+        match expr {
+            Expr::Column(ident) => self.push_sql(&ident.to_sql()),
+            Expr::Literal(l) => self.push_sql(&l.to_sql()),
+            Expr::Bind(param) => {
+                self.tokens.push(TmpQueryToken::Parameter);
+                self.parameters.push(param);
+            },
+            Expr::Function(name, args) => {
+                self.push_sql(&format!("{}(", name));
+                self.write_list(args);
+                self.push_sql(")");
+            },
+            Expr::Binary(lhs, op, rhs) => {
+                let precedence = op.precedence();
+                self.write(*lhs, precedence, !op.is_left_assoc());
+                self.push_sql(&format!(" {} ", op.to_sql()));
+                self.write(*rhs, precedence, true);
+            },
+            Expr::Unary(op, operand) => {
+                let precedence = op.precedence();
+                match op {
+                    UnaryOp::Not => {
+                        self.push_sql("NOT ");
+                        self.write(*operand, precedence, false);
+                    },
+                    UnaryOp::Neg => {
+                        self.push_sql("-");
+                        self.write(*operand, precedence, true);
+                    },
+                    UnaryOp::IsNull => {
+                        self.write(*operand, precedence, true);
+                        self.push_sql(" IS NULL");
+                    },
+                    UnaryOp::IsNotNull => {
+                        self.write(*operand, precedence, true);
+                        self.push_sql(" IS NOT NULL");
+                    },
+                }
+            },
+            Expr::Case(case) => {
+                if case.whens.is_empty() {
+                    self.push_error("CASE requires at least 1 WHEN");
+                }
+                self.push_sql("CASE");
+                if let Some(operand) = case.operand {
+                    self.push_sql(" ");
+                    self.write(*operand, 0, false);
+                }
+                for (condition, then) in case.whens {
+                    self.push_sql(" WHEN ");
+                    self.write(condition, 0, false);
+                    self.push_sql(" THEN ");
+                    self.write(then, 0, false);
+                }
+                if let Some(otherwise) = case.otherwise {
+                    self.push_sql(" ELSE ");
+                    self.write(*otherwise, 0, false);
+                }
+                self.push_sql(" END");
+            },
+            Expr::Over(expr, over) => {
+                self.write_operand(*expr);
+                match over {
+                    Over::Named(name) => self.push_sql(&format!(" OVER {}", name)),
+                    Over::Window(window) => {
+                        self.push_sql(" OVER (");
+                        self.write_window(*window);
+                        self.push_sql(")");
+                    },
+                }
+            },
+            Expr::Cast(expr, type_name) => {
+                self.push_sql("CAST(");
+                self.write(*expr, 0, false);
+                self.push_sql(&format!(" AS {})", type_name));
+            },
+            Expr::Tuple(exprs) => {
+                self.push_sql("(");
+                self.write_list(exprs);
+                self.push_sql(")");
+            },
+            Expr::Raw(tokens, parameters) => {
+                for token in tokens.0 {
+                    match token {
+                        TmpQueryToken::Sql(s) => self.push_sql(&s),
+                        token => self.tokens.push(token),
+                    }
+                }
+                self.parameters.extend(parameters);
+            },
+        }
+        if wrap {
+            self.push_sql(")");
+        }
+    }
+}
+
+// arithmetic with the usual operators, col("price") * bind(&qty)
+impl<'a, S: Into<Expr<'a>>> std::ops::Add<S> for Expr<'a> {
+    type Output = Expr<'a>;
+    fn add(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Add, rhs)
+    }
+}
+impl<'a, S: Into<Expr<'a>>> std::ops::Sub<S> for Expr<'a> {
+    type Output = Expr<'a>;
+    fn sub(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Sub, rhs)
+    }
+}
+impl<'a, S: Into<Expr<'a>>> std::ops::Mul<S> for Expr<'a> {
+    type Output = Expr<'a>;
+    fn mul(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Mul, rhs)
+    }
+}
+impl<'a, S: Into<Expr<'a>>> std::ops::Div<S> for Expr<'a> {
+    type Output = Expr<'a>;
+    fn div(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Div, rhs)
+    }
+}
+impl<'a, S: Into<Expr<'a>>> std::ops::Rem<S> for Expr<'a> {
+    type Output = Expr<'a>;
+    fn rem(self, rhs: S) -> Expr<'a> {
+        self.binary(BinaryOp::Mod, rhs)
+    }
+}
+impl<'a> std::ops::Neg for Expr<'a> {
+    type Output = Expr<'a>;
+    fn neg(self) -> Expr<'a> {
+        neg(self)
+    }
+}
+
+impl<'a> From<&str> for Expr<'a> {
+    fn from(sql: &str) -> Self {
+        Expr::Raw(TmpQueryTokens(vec![TmpQueryToken::Sql(sql.to_owned())]), Vec::new())
+    }
+}
+impl<'a> From<String> for Expr<'a> {
+    fn from(sql: String) -> Self {
+        Expr::Raw(TmpQueryTokens(vec![TmpQueryToken::Sql(sql)]), Vec::new())
+    }
+}
+impl<'a> From<FormatQueryTup<'a>> for Expr<'a> {
+    fn from(ftup: FormatQueryTup<'a>) -> Self {
+        Expr::Raw(ftup.0, ftup.1)
+    }
+}
+impl<'a> From<Ident> for Expr<'a> {
+    fn from(ident: Ident) -> Self {
+        Expr::Column(ident)
+    }
+}
+impl<'a> From<Literal> for Expr<'a> {
+    fn from(l: Literal) -> Self {
+        Expr::Literal(l)
+    }
+}
+impl<'a> From<Case<'a>> for Expr<'a> {
+    fn from(case: Case<'a>) -> Self {
+        Expr::Case(case)
+    }
+}
+impl<'a> From<Expr<'a>> for FormatQueryTup<'a> {
+    fn from(expr: Expr<'a>) -> Self {
+        expr.into_format_query()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::query_token::format_query;
+    use crate::postgres::query_field::ParamIter;
+    fn build(expr: Expr) -> (String, usize) {
+        let (tokens, parameters) = expr.into_format_query();
+        let built = tokens.into_query_tokens(0).build(&mut ParamIter::default()).unwrap();
+        assert_eq!(built.parameters_loc.len(), parameters.len());
+        (built.query, parameters.len())
+    }
+    #[test]
+    fn test_expr_basic() {
+        assert_eq!(build(col("u.name").eq(bind(&"foo"))), ("\"u\".\"name\" = $1".to_owned(), 1));
+        assert_eq!(build(col("u.*")).0, "\"u\".*");
+        assert_eq!(build(col("we\"ird")).0, "\"we\"\"ird\"");
+        assert_eq!(build(lit("it's")).0, "'it''s'");
+        assert_eq!(build(func("COALESCE", vec![col("nickname"), col("name"), lit("anonymous")])).0, "COALESCE(\"nickname\", \"name\", 'anonymous')");
+        assert_eq!(build(cast(bind(&1), "bigint")).0, "CAST($1 AS bigint)");
+        assert_eq!(build(col("deleted_at").is_null().and(not(col("banned")))).0, "\"deleted_at\" IS NULL AND NOT \"banned\"");
+        assert_eq!(build(col("tags").op("&&", "ARRAY['a']")).0, "\"tags\" && (ARRAY['a'])");
+    }
+    #[test]
+    fn test_expr_precedence() {
+        let expr = col("a").eq(bind(&1)).or(col("b").eq(bind(&2))).and(col("c").gt(lit(0)));
+        assert_eq!(build(expr), ("(\"a\" = $1 OR \"b\" = $2) AND \"c\" > 0".to_owned(), 2));
+        let expr = (col("a") - (col("b") - col("c"))) * lit(2) % bind(&7);
+        assert_eq!(build(expr).0, "(\"a\" - (\"b\" - \"c\")) * 2 % $1");
+        assert_eq!(build(-lit(-1)).0, "-(-1)");
+        assert_eq!(build(col("a") - lit(-1)).0, "\"a\" - -1");
+        assert_eq!(build(col("a").eq(col("b")).eq(lit(true))).0, "(\"a\" = \"b\") = TRUE");
+    }
+    #[test]
+    fn test_expr_case_and_raw() {
+        let expr = case()
+            .when(col("age").lt(bind(&18)), lit("minor"))
+            .otherwise(lit("adult"))
+            .end();
+        assert_eq!(build(expr).0, "CASE WHEN \"age\" < $1 THEN 'minor' ELSE 'adult' END");
+        let expr = case_of(col("status")).when(lit(1), lit("active")).end();
+        assert_eq!(build(expr).0, "CASE \"status\" WHEN 1 THEN 'active' END");
+        let expr = Expr::from(format_query("LOWER(name) = {}", vec![&"foo"])).and(col("id").gt(bind(&1)));
+        assert_eq!(build(expr), ("(LOWER(name) = $1) AND \"id\" > $2".to_owned(), 2));
+        let expr = Expr::from(format_query("a = {} OR b = {}", vec![&1, &2])).and(col("c").eq(bind(&3)));
+        assert_eq!(build(expr).0, "(a = $1 OR b = $2) AND \"c\" = $3");
+        let expr = Expr::from("a = 1").or("b = 2").and(not("c"));
+        assert_eq!(build(expr).0, "(a = 1 OR b = 2) AND NOT c");
+        assert_eq!(build(Expr::from("u.id").gt(bind(&1))).0, "u.id > $1");
+        let (tokens, _) = Expr::from(case()).into_format_query();
+        assert!(tokens.into_query_tokens(0).build(&mut ParamIter::default()).is_err());
+    }
+    #[test]
+    fn test_expr_window() {
+        let expr = func("row_number", vec![]).over(window()
+            .partition_by(col("team_id"))
+            .order_by(col("score"), "DESC")
+            .order_by(col("id"), ""));
+        assert_eq!(build(expr).0, "row_number() OVER (PARTITION BY \"team_id\" ORDER BY \"score\" DESC, \"id\")");
+        let expr = func("SUM", vec![col("amount")]).over(window()
+            .order_by(col("created_at"), "ASC")
+            .rows(FrameBound::Preceding(bind(&6)), FrameBound::CurrentRow));
+        assert_eq!(build(expr), ("SUM(\"amount\") OVER (ORDER BY \"created_at\" ASC ROWS BETWEEN $1 PRECEDING AND CURRENT ROW)".to_owned(), 1));
+        let expr = func("SUM", vec![col("amount")]).over(window()
+            .base("w")
+            .range(FrameBound::Preceding("'7 days'::interval".into()), FrameBound::Following(lit(0))));
+        assert_eq!(build(expr).0, "SUM(\"amount\") OVER (w RANGE BETWEEN '7 days'::interval PRECEDING AND 0 FOLLOWING)");
+        assert_eq!(build(func("rank", vec![]).over_named("w") + lit(1)).0, "rank() OVER w + 1");
+        assert_eq!(build(func("COUNT", vec![col("*")]).over(window())).0, "COUNT(*) OVER ()");
+        let (tokens, _) = func("SUM", vec![col("amount")]).over(window()
+            .rows(FrameBound::CurrentRow, FrameBound::Preceding(lit(1))))
+            .into_format_query();
+        assert!(tokens.into_query_tokens(0).build(&mut ParamIter::default()).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! quoted identifier for table, column and constraint names
+//!
+//! `Ident::new` trusts the name and quotes every part, so it is for names written in the code.
+//! `Ident::parse` is the strict mode for names that come from the user, such as a sort column from a request:
+//! every part has to be a plain name or an already quoted name, anything else is an error instead of sql.
+//! An `Alias`, such as a WITH name or a window name, given as a string is written as is, give an Ident to quote it
+use std::fmt;
+use crate::error::{Error, ErrorContext};
+use crate::postgres::static_constant::FIELD_ESCAPE;
+
+/// "schema"."table"."column", each part is quoted and " inside a part is doubled, * is not quoted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ident(Vec<String>);
+
+/// "name" with the " inside doubled, * is kept as is
+pub(crate) fn quote(part: &str) -> String {
+    if part == "*" {
+        return part.to_owned();
+    }
+    format!("{}{}{}", FIELD_ESCAPE, part.replace(FIELD_ESCAPE, &FIELD_ESCAPE.repeat(2)), FIELD_ESCAPE)
+}
+
+fn invalid(input: &str) -> Error {
+    Error::InvalidIdentifier { context: ErrorContext::identifier(input) }
+}
+
+impl Ident {
+    /// split the path on ".", use from_parts when a part contains "."
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        Ident(path.into().split('.').map(|s| s.to_owned()).collect())
+    }
+    pub fn from_parts<S: Into<String>>(parts: Vec<S>) -> Self {
+        Ident(parts.into_iter().map(|s| s.into()).collect())
+    }
+    /// a plain part is [A-Za-z_][A-Za-z0-9_$]* and is lowercased like postgres does for an unquoted name,
+    /// a quoted part keeps its case and can contain anything, with " written as ""
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut parts: Vec<String> = Vec::new();
+        let mut cur = 0;
+        loop {
+            let mut part = String::new();
+            if chars.get(cur) == Some(&'"') {
+                cur += 1;
+                loop {
+                    match (chars.get(cur), chars.get(cur + 1)) {
+                        (Some('"'), Some('"')) => {
+                            part.push('"');
+                            cur += 2;
+                        },
+                        (Some('"'), _) => {
+                            cur += 1;
+                            break;
+                        },
+                        (Some(c), _) => {
+                            part.push(*c);
+                            cur += 1;
+                        },
+                        (None, _) => return Err(invalid(input)),
+                    }
+                }
+            } else {
+                match chars.get(cur) {
+                    Some(c) if c.is_ascii_alphabetic() || *c == '_' => {},
+                    _ => return Err(invalid(input)),
+                }
+                while let Some(c) = chars.get(cur).filter(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '$') {
+                    part.push(c.to_ascii_lowercase());
+                    cur += 1;
+                }
+            }
+            if part.is_empty() {
+                return Err(invalid(input));
+            }
+            parts.push(part);
+            match chars.get(cur) {
+                None => return Ok(Ident(parts)),
+                Some('.') => cur += 1,
+                Some(_) => return Err(invalid(input)),
+            }
+        }
+    }
+    pub fn parts(&self) -> &[String] {
+        &self.0
+    }
+    pub fn to_sql(&self) -> String {
+        self.0.iter().map(|part| quote(part)).collect::<Vec<String>>().join(".")
+    }
+    // the unquoted path used in error messages
+    pub(crate) fn display_name(&self) -> String {
+        self.0.join(".")
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_sql())
+    }
+}
+
+impl From<&str> for Ident {
+    fn from(path: &str) -> Self {
+        Ident::new(path)
+    }
+}
+impl From<String> for Ident {
+    fn from(path: String) -> Self {
+        Ident::new(path)
+    }
+}
+impl From<&Ident> for Ident {
+    fn from(ident: &Ident) -> Self {
+        ident.clone()
+    }
+}
+
+/// an alias, a WITH name or a window name, a string is written as is and an Ident is quoted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alias {
+    Raw(String),
+    Quoted(Ident),
+}
+
+impl Alias {
+    pub fn to_sql(&self) -> String {
+        match self {
+            Alias::Raw(name) => name.clone(),
+            Alias::Quoted(ident) => ident.to_sql(),
+        }
+    }
+    // a schema before the name, which an alias can't have
+    pub(crate) fn is_qualified(&self) -> bool {
+        match self {
+            Alias::Raw(name) => name.contains('.'),
+            Alias::Quoted(ident) => ident.parts().len() != 1,
+        }
+    }
+    pub(crate) fn display_name(&self) -> String {
+        match self {
+            Alias::Raw(name) => name.clone(),
+            Alias::Quoted(ident) => ident.display_name(),
+        }
+    }
+}
+
+impl From<&str> for Alias {
+    fn from(name: &str) -> Self {
+        Alias::Raw(name.to_owned())
+    }
+}
+impl From<String> for Alias {
+    fn from(name: String) -> Self {
+        Alias::Raw(name)
+    }
+}
+impl From<Ident> for Alias {
+    fn from(ident: Ident) -> Self {
+        Alias::Quoted(ident)
+    }
+}
+impl From<&Ident> for Alias {
+    fn from(ident: &Ident) -> Self {
+        Alias::Quoted(ident.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_ident_quote() {
+        assert_eq!(Ident::new("user").to_sql(), "\"user\"");
+        assert_eq!(Ident::new("public.user.id").to_sql(), "\"public\".\"user\".\"id\"");
+        assert_eq!(Ident::new("u.*").to_sql(), "\"u\".*");
+        assert_eq!(Ident::new("a\"b").to_sql(), "\"a\"\"b\"");
+        assert_eq!(Ident::from_parts(vec!["my.schema", "user"]).to_sql(), "\"my.schema\".\"user\"");
+        assert_eq!(Alias::from(Ident::new("x\"; DROP TABLE user; --")).to_sql(), "\"x\"\"; DROP TABLE user; --\"");
+        assert_eq!(Alias::from("u").to_sql(), "u");
+    }
+    #[test]
+    fn test_ident_parse() {
+        assert_eq!(Ident::parse("public.User_1").unwrap(), Ident::from_parts(vec!["public", "user_1"]));
+        assert_eq!(Ident::parse("\"My \"\"Table\"\"\".id").unwrap(), Ident::from_parts(vec!["My \"Table\"", "id"]));
+        assert_eq!(Ident::parse("\"a.b\"").unwrap().to_sql(), "\"a.b\"");
+        for input in &["", "id;", "id DESC", "1id", "a..b", "a.", "\"\"", "\"open", "\"a\"b", "name) --", "*"] {
+            let error = Ident::parse(input).unwrap_err();
+            assert!(matches!(error, Error::InvalidIdentifier { .. }), "{}", input);
+            assert_eq!(error.context().identifier.as_deref(), Some(*input));
+        }
+    }
+}
@@ -1,72 +1,243 @@
-use crate::error::Error;
+use std::ops::Range;
+use crate::error::{Error, ErrorContext, BuilderKind, Clause};
 use crate::postgres::query_table::{QueryTables, QueryTable};
-use crate::postgres::query_field::{QueryWithParams, ParameterValueAsRef};
-use crate::postgres::query_token::{QueryTokens, QueryToken, FormatQueryTup};
-use crate::postgres::query_set::{QuerySets};
-use crate::postgres::query_return::{QueryReturns, QueryReturnField};
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParameterValue, ParamIter};
+use crate::postgres::expr::Expr;
+use crate::postgres::query_token::{QueryTokens, QueryToken};
+use crate::postgres::query_set::{QueryRows};
+use crate::postgres::query_return::QueryReturns;
+use crate::postgres::query_conflict::{QueryConflict, ConflictTarget, ConflictAction};
+use crate::postgres::query_where::{WhereOperator};
+use crate::postgres::select_builder::SqlSelect;
+use crate::postgres::query_with::{QueryWiths, WithQuery};
+use crate::postgres::query_subquery::{SubQuery};
+use crate::postgres::static_constant::MAX_PARAMETER;
+use crate::postgres::ident::{Ident, Alias};
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct SqlInsert<'a> {
+    _withs: QueryWiths,
     _tables: QueryTables, // to support update tableA, tableB set ...
-    _sets: QuerySets,
+    _sets: QueryRows,
     _returns: QueryReturns,
-    _parameters: Vec<ParameterValueAsRef<'a>>,
+    _conflict: Option<QueryConflict>,
+    _select: Option<(Vec<Ident>, QueryTokens)>,
+    _parameters: Vec<ParameterValue<'a>>,
 }
 
 impl<'a> SqlInsert<'a> {
     pub fn new() -> SqlInsert<'static> {
         SqlInsert::default()
     }
-    pub fn build(&self) -> Result<QueryWithParams, Error> {
-        let mut param_iter = 1;
-        if self._tables.len() != 1 {
-            return Err(Error::Syntax("insert can only have 1 table".to_owned()))
+    pub fn build(&self) -> Result<QueryWithParams<'a>, Error> {
+        self.build_rows(0..self._sets.len())
+    }
+    /// same as build, but split the rows into several statements when the parameters exceed the postgres limit of 65535.
+    /// Every statement repeats the rest of the insert, including WITH, so a WITH that modifies data is run once per chunk
+    pub fn build_chunks(&self) -> Result<Vec<QueryWithParams<'a>>, Error> {
+        if self._select.is_some() {
+            return Ok(vec![self.build()?]);
+        }
+        self.build_chunks_with_max(MAX_PARAMETER)
+    }
+    fn build_chunks_with_max(&self, max: usize) -> Result<Vec<QueryWithParams<'a>>, Error> {
+        // WITH, ON CONFLICT and RETURNING are repeated in each chunk
+        let fixed = self._withs.parameter_len()
+            + self._conflict.as_ref().map(|c| c.parameter_len()).unwrap_or(0)
+            + self._returns.parameter_len();
+        let mut built: Vec<QueryWithParams<'a>> = Vec::new();
+        let ranges = self._sets.chunk_ranges(fixed, max).map_err(|e| e.within(BuilderKind::Insert, Clause::Values))?;
+        for range in ranges {
+            built.push(self.build_rows(range)?);
         }
+        Ok(built)
+    }
+    fn build_rows(&self, range: Range<usize>) -> Result<QueryWithParams<'a>, Error> {
+        let mut param_iter = ParamIter::default();
+        let built = self.build_rows_loc(range, &mut param_iter)?;
+        Ok(QueryWithParams {
+            query: built.query,
+            parameters: built.parameters_loc.into_iter().map(|ploc| self._parameters[ploc].clone()).collect(),
+        })
+    }
+    pub(crate) fn build_loc(&self, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        self.build_rows_loc(0..self._sets.len(), param_iter)
+    }
+    fn build_rows_loc(&self, range: Range<usize>, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let at = |clause: Clause| move |e: Error| e.within(BuilderKind::Insert, clause);
         let mut vec: Vec<String> = Vec::new();
-        let built_for_table = self._tables.build(&mut param_iter)?;
-        let built_for_create = self._sets.build_for_create(&mut param_iter)?;
-        let mut p: Vec<ParameterValueAsRef> = Vec::new();
-        for ploc in built_for_table.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let mut p: Vec<usize> = Vec::new();
+        if self._withs.len() > 0 {
+            let built_for_with = self._withs.build(param_iter).map_err(at(Clause::With))?;
+            vec.push(built_for_with.query);
+            p.extend(built_for_with.parameters_loc);
         }
-        for ploc in built_for_create.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let built_for_table = self._tables.build(param_iter).map_err(at(Clause::From))?;
+        p.extend(built_for_table.parameters_loc);
+        if let Some((columns, select)) = &self._select {
+            if self._sets.len() > 0 {
+                return Err(Error::SetWithSelect { context: ErrorContext::default() }).map_err(at(Clause::Values));
+            }
+            if columns.is_empty() {
+                return Err(Error::EmptyColumns { context: ErrorContext::default() }).map_err(at(Clause::Values));
+            }
+            let built_for_select = select.build(param_iter).map_err(at(Clause::Values))?;
+            let escaped: Vec<String> = columns.iter().map(|c| c.to_sql()).collect();
+            vec.push(format!("INSERT INTO {}({}) {}", built_for_table.query, escaped.join(","), built_for_select.query));
+            p.extend(built_for_select.parameters_loc);
+        } else {
+            let built_for_create = self._sets.build_for_create(range, param_iter).map_err(at(Clause::Values))?;
+            p.extend(built_for_create.parameters_loc);
+            vec.push(format!("INSERT INTO {}{}", built_for_table.query, built_for_create.query));
+        }
+        if let Some(conflict) = &self._conflict {
+            let built_for_conflict = conflict.build(param_iter).map_err(at(Clause::OnConflict))?;
+            vec.push(built_for_conflict.query);
+            p.extend(built_for_conflict.parameters_loc);
         }
-        vec.push(format!("INSERT INTO {}{}", built_for_table.query, built_for_create.query));
         if self._returns.len() > 0 {
-            let built_for_return: String = self._returns.build()?;
-            vec.push(format!("RETURNING {}", built_for_return));
+            let built_for_return = self._returns.build(param_iter).map_err(at(Clause::Returning))?;
+            vec.push(format!("RETURNING {}", built_for_return.query));
+            p.extend(built_for_return.parameters_loc);
         }
-        Ok(QueryWithParams {
+        Ok(QueryWithParamsLoc {
             query: vec.join(" "),
-            parameters: p,
+            parameters_loc: p,
         })
     }
-    pub fn returning<S: Into<QueryReturnField>>(&mut self, field: S) -> &mut Self {
-        self._returns.push(field.into());
+    pub(crate) fn into_parts(self) -> (SqlInsert<'static>, Vec<ParameterValue<'a>>) {
+        let insert = SqlInsert {
+            _withs: self._withs,
+            _tables: self._tables,
+            _sets: self._sets,
+            _returns: self._returns,
+            _conflict: self._conflict,
+            _select: self._select,
+            _parameters: Vec::new(),
+        };
+        (insert, self._parameters)
+    }
+    pub fn returning<E: Into<Expr<'a>>>(&mut self, field: E) -> &mut Self {
+        self._returns.push_expr(field.into(), None, &mut self._parameters);
+        self
+    }
+    pub fn returning_as<E: Into<Expr<'a>>, T: Into<Alias>>(&mut self, field: E, alias: T) -> &mut Self {
+        self._returns.push_expr(field.into(), Some(alias.into().to_sql()), &mut self._parameters);
         self
     }
     pub fn table<S: Into<QueryTable>>(&mut self, table: S) -> &mut Self {
         self._tables.push(table.into());
         self
     }
-    pub fn set<S: Into<String>, T>(&mut self, field: S, param: &'a T) -> &mut Self where T: postgres_types::ToSql + std::marker::Sync + 'a {
-        self._parameters.push(param);
+    pub fn set<S: Into<Ident>, P: Into<ParameterValue<'a>>>(&mut self, field: S, param: P) -> &mut Self {
+        self._parameters.push(param.into());
         self._sets.set((field.into(), QueryTokens(vec![QueryToken::ParameterLoc(self._parameters.len() - 1)])));
         self
     }
-    pub fn set_raw<S: Into<String>>(&mut self, field: S, tup: FormatQueryTup<'a>) -> &mut Self{
+    pub fn set_raw<S: Into<Ident>, E: Into<Expr<'a>>>(&mut self, field: S, expr: E) -> &mut Self {
+        let tup = expr.into().into_format_query();
         let len = self._parameters.len();
         self._parameters.extend(tup.1);
-        let qtokens = (tup.0).to_query_tokens(len);
+        let qtokens = (tup.0).into_query_tokens(len);
         self._sets.set((field.into(), qtokens));
         self
     }
+    /// INSERT INTO "table"("a","b") SELECT ..., the rows come from the select instead of set()
+    pub fn from_select<S: Into<Ident> + Clone>(&mut self, columns: &[S], select: SqlSelect<'a>) -> &mut Self {
+        let len = self._parameters.len();
+        let (tokens, parameters) = SubQuery::from(select).into_format_query();
+        self._parameters.extend(parameters);
+        self._select = Some((columns.iter().cloned().map(|c| c.into()).collect(), tokens.into_query_tokens(len)));
+        self
+    }
+    /// start a new row for multi-row insert, the sets of every row must have the same fields as the first row
+    pub fn new_row(&mut self) -> &mut Self {
+        self._sets.new_row();
+        self
+    }
+    fn conflict(&mut self) -> &mut QueryConflict {
+        self._conflict.get_or_insert_with(QueryConflict::default)
+    }
+    /// ON CONFLICT ("a","b"), accept a column or a vec of columns
+    pub fn on_conflict<S: Into<ConflictTarget>>(&mut self, target: S) -> &mut Self {
+        self.conflict().target = Some(target.into());
+        self
+    }
+    /// ON CONFLICT ON CONSTRAINT "name"
+    pub fn on_conflict_on_constraint<S: Into<Ident>>(&mut self, constraint: S) -> &mut Self {
+        self.conflict().target = Some(ConflictTarget::Constraint(constraint.into()));
+        self
+    }
+    /// ON CONFLICT ("a") WHERE ..., to infer a partial unique index
+    pub fn on_conflict_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        let qtokens = (ftup.0).into_query_tokens(len);
+        let conflict = self.conflict();
+        if conflict.target_wheres.len() > 0 {
+            conflict.target_wheres.push(WhereOperator::And);
+        }
+        conflict.target_wheres.extend(qtokens.into());
+        self
+    }
+    pub fn do_nothing(&mut self) -> &mut Self {
+        self.conflict().action = Some(ConflictAction::Nothing);
+        self
+    }
+    pub fn do_update_set<S: Into<Ident>, P: Into<ParameterValue<'a>>>(&mut self, field: S, param: P) -> &mut Self {
+        self._parameters.push(param.into());
+        let qtokens = QueryTokens(vec![QueryToken::ParameterLoc(self._parameters.len() - 1)]);
+        let conflict = self.conflict();
+        conflict.action = Some(ConflictAction::Update);
+        conflict.sets.set((field.into(), qtokens));
+        self
+    }
+    /// the query can refer to the proposed row with EXCLUDED."field"
+    pub fn do_update_set_raw<S: Into<Ident>, E: Into<Expr<'a>>>(&mut self, field: S, expr: E) -> &mut Self {
+        let tup = expr.into().into_format_query();
+        let len = self._parameters.len();
+        self._parameters.extend(tup.1);
+        let qtokens = (tup.0).into_query_tokens(len);
+        let conflict = self.conflict();
+        conflict.action = Some(ConflictAction::Update);
+        conflict.sets.set((field.into(), qtokens));
+        self
+    }
+    /// "field"=EXCLUDED."field"
+    pub fn do_update_set_excluded<S: Into<Ident>>(&mut self, field: S) -> &mut Self {
+        let field = field.into();
+        let qtokens = QueryTokens(vec![QueryToken::Sql(format!("EXCLUDED.{}", field.to_sql()))]);
+        let conflict = self.conflict();
+        conflict.action = Some(ConflictAction::Update);
+        conflict.sets.set((field, qtokens));
+        self
+    }
+    /// DO UPDATE SET ... WHERE ...
+    pub fn do_update_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        let qtokens = (ftup.0).into_query_tokens(len);
+        let conflict = self.conflict();
+        if conflict.wheres.len() > 0 {
+            conflict.wheres.push(WhereOperator::And);
+        }
+        conflict.wheres.extend(qtokens.into());
+        self
+    }
+}
+
+impl<'a> WithQuery<'a> for SqlInsert<'a> {
+    fn withs_mut(&mut self) -> (&mut QueryWiths, &mut Vec<ParameterValue<'a>>) {
+        (&mut self._withs, &mut self._parameters)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::postgres::query_token::{format_query};
+    use crate::postgres::delete_builder::SqlDelete;
     use super::*;
     #[test]
     fn test_insert_builder_1() {
@@ -83,4 +254,205 @@ mod tests {
         assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"spouse\",\"age\",\"name\") VALUES ($1,$2,LOWER($3)) RETURNING id, age");
         assert_eq!(format!("{:?}", qbuild.parameters), "[None, 1337, \"foo\"]");
     }
+
+    #[test]
+    fn test_insert_builder_multi_row() {
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .table("user")
+            .set("name", &"foo")
+            .set_raw("age", format_query("{} + 1", vec![&1]))
+            .new_row()
+            .set("name", &"bar")
+            .set_raw("age", format_query("{} + 1", vec![&2]))
+            .returning("id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"name\",\"age\") VALUES ($1,$2 + 1),($3,$4 + 1) RETURNING id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"foo\", 1, \"bar\", 2]");
+    }
+
+    #[test]
+    fn test_insert_builder_multi_row_mismatch() {
+        let mut sql_insert = SqlInsert::new();
+        sql_insert
+            .table("user")
+            .set("name", &"foo")
+            .set("age", &1)
+            .new_row()
+            .set("age", &2)
+            .set("name", &"bar");
+        let error = sql_insert.build().unwrap_err();
+        assert!(matches!(error, Error::RowColumnMismatch { row: 1, .. }));
+        assert_eq!(error.to_string(), "row 1 columns does not match with the first row [builder: INSERT, clause: VALUES, identifier: age]");
+    }
+
+    #[test]
+    fn test_insert_builder_chunks() {
+        let ids: Vec<i32> = (0..5).collect();
+        let mut sql_insert = SqlInsert::new();
+        sql_insert.table("user");
+        for id in &ids {
+            sql_insert.new_row().set("id", id).set("name", &"foo");
+        }
+        sql_insert.on_conflict("id").do_update_set("name", &"bar");
+        let chunks = sql_insert.build_chunks_with_max(5).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].query, "INSERT INTO \"user\"(\"id\",\"name\") VALUES ($1,$2),($3,$4) ON CONFLICT (\"id\") DO UPDATE SET \"name\"=$5");
+        assert_eq!(chunks[2].query, "INSERT INTO \"user\"(\"id\",\"name\") VALUES ($1,$2) ON CONFLICT (\"id\") DO UPDATE SET \"name\"=$3");
+        assert_eq!(format!("{:?}", chunks[1].parameters), "[2, \"foo\", 3, \"foo\", \"bar\"]");
+        assert_eq!(sql_insert.build_chunks().unwrap().len(), 1);
+        assert!(matches!(sql_insert.build_chunks_with_max(2), Err(Error::ParameterOverflow { max: 2, .. })));
+    }
+
+    #[test]
+    fn test_insert_builder_chunks_with() {
+        let ids: Vec<i32> = (0..3).collect();
+        let mut sql_select = SqlSelect::new();
+        sql_select.select("id").table("team").and_where(format_query("code = {} OR code = {}", vec![&"a", &"b"]));
+        let mut sql_insert = SqlInsert::new();
+        sql_insert.with("t", sql_select).table("user");
+        for id in &ids {
+            sql_insert.new_row().set("id", id);
+        }
+        let chunks = sql_insert.build_chunks_with_max(4).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].query, "WITH t AS (SELECT id FROM \"team\" WHERE code = $1 OR code = $2) INSERT INTO \"user\"(\"id\") VALUES ($3),($4)");
+        assert_eq!(format!("{:?}", chunks[1].parameters), "[\"a\", \"b\", 2]");
+        assert!(matches!(sql_insert.build_chunks_with_max(2), Err(Error::ParameterOverflow { max: 2, .. })));
+    }
+
+    #[test]
+    fn test_insert_builder_chunks_empty() {
+        let mut sql_insert = SqlInsert::new();
+        sql_insert.table("user");
+        let error = sql_insert.build_chunks().unwrap_err();
+        assert!(matches!(error, Error::EmptySet { .. }));
+        assert_eq!(error.context(), sql_insert.build().unwrap_err().context());
+        assert_eq!(error.context().clause, Some(Clause::Values));
+    }
+
+    #[test]
+    fn test_insert_builder_chunks_returning() {
+        use crate::postgres::expr::{col, bind};
+        let ids: Vec<i32> = (0..3).collect();
+        let mut sql_insert = SqlInsert::new();
+        sql_insert.table("user");
+        for id in &ids {
+            sql_insert.new_row().set("id", id);
+        }
+        sql_insert.returning(col("n") + bind(&5));
+        let chunks = sql_insert.build_chunks_with_max(3).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].query, "INSERT INTO \"user\"(\"id\") VALUES ($1),($2) RETURNING \"n\" + $3");
+        assert_eq!(format!("{:?}", chunks[1].parameters), "[2, 5]");
+    }
+
+    #[test]
+    fn test_insert_builder_from_select() {
+        let mut sql_select = SqlSelect::new();
+        sql_select
+            .select("id")
+            .select("name")
+            .table("user")
+            .and_where(format_query("created_at < {}", vec![&"2020-01-01"]));
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .table("user_archive")
+            .from_select(&["id", "name"], sql_select)
+            .on_conflict("id")
+            .do_update_set("name", &"archived")
+            .returning("id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"user_archive\"(\"id\",\"name\") SELECT id, name FROM \"user\" WHERE created_at < $1 ON CONFLICT (\"id\") DO UPDATE SET \"name\"=$2 RETURNING id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"2020-01-01\", \"archived\"]");
+        sql_insert.set("id", &1);
+        assert!(matches!(sql_insert.build(), Err(Error::SetWithSelect { .. })));
+    }
+
+    #[test]
+    fn test_insert_builder_on_conflict_do_nothing() {
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .table("user")
+            .set("id", &1)
+            .do_nothing()
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"id\") VALUES ($1) ON CONFLICT DO NOTHING");
+        let qbuild = sql_insert
+            .on_conflict_on_constraint("user_pkey")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"id\") VALUES ($1) ON CONFLICT ON CONSTRAINT \"user_pkey\" DO NOTHING");
+    }
+
+    #[test]
+    fn test_insert_builder_on_conflict_do_update() {
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .table("user")
+            .set("email", &"foo@example.com")
+            .set("name", &"foo")
+            .set("login", &1)
+            .on_conflict(vec!["email"])
+            .on_conflict_where(format_query("deleted_at IS NULL", vec![]))
+            .do_update_set_excluded("name")
+            .do_update_set_raw("login", format_query("\"user\".\"login\" + {}", vec![&1]))
+            .do_update_set("updated_by", &"system")
+            .do_update_where(format_query("\"user\".\"locked\" = {}", vec![&false]))
+            .returning("id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"user\"(\"email\",\"name\",\"login\") VALUES ($1,$2,$3) ON CONFLICT (\"email\") WHERE deleted_at IS NULL DO UPDATE SET \"name\"=EXCLUDED.\"name\",\"login\"=\"user\".\"login\" + $4,\"updated_by\"=$5 WHERE \"user\".\"locked\" = $6 RETURNING id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"foo@example.com\", \"foo\", 1, 1, \"system\", false]");
+    }
+
+    #[test]
+    fn test_insert_builder_on_conflict_error() {
+        let mut sql_insert = SqlInsert::new();
+        sql_insert
+            .table("user")
+            .set("id", &1)
+            .do_update_set_excluded("id");
+        let error = sql_insert.build().unwrap_err();
+        assert!(matches!(error, Error::ConflictWithoutTarget { .. }));
+        assert_eq!(error.context().clause, Some(Clause::OnConflict));
+        sql_insert.on_conflict_on_constraint("user_pkey").on_conflict_where(format_query("id > 0", vec![]));
+        let error = sql_insert.build().unwrap_err();
+        assert!(matches!(error, Error::ConstraintWithWhere { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("user_pkey"));
+    }
+
+    #[test]
+    fn test_insert_builder_ident() {
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .table(Ident::new("audit.log"))
+            .set("na\"me", &"foo")
+            .set(Ident::parse("\"Level\"").unwrap(), &1)
+            .on_conflict(vec![Ident::new("na\"me")])
+            .do_update_set_excluded(Ident::new("Level"))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "INSERT INTO \"audit\".\"log\"(\"na\"\"me\",\"Level\") VALUES ($1,$2) ON CONFLICT (\"na\"\"me\") DO UPDATE SET \"Level\"=EXCLUDED.\"Level\"");
+    }
+
+    #[test]
+    fn test_insert_builder_with_data_modifying() {
+        let mut sql_delete = SqlDelete::new();
+        sql_delete
+            .table("user")
+            .and_where(format_query("created_at < {}", vec![&"2020-01-01"]))
+            .returning("*");
+        let mut sql_select = SqlSelect::new();
+        sql_select
+            .select("*")
+            .table("moved");
+        let mut sql_insert = SqlInsert::new();
+        let qbuild = sql_insert
+            .with_materialized("moved", sql_delete)
+            .table("user_archive")
+            .from_select(&["id", "name"], sql_select)
+            .on_conflict("id")
+            .do_nothing()
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH moved AS MATERIALIZED (DELETE FROM \"user\" WHERE created_at < $1 RETURNING *) INSERT INTO \"user_archive\"(\"id\",\"name\") SELECT * FROM \"moved\" ON CONFLICT (\"id\") DO NOTHING");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"2020-01-01\"]");
+    }
 }
@@ -1,20 +1,51 @@
 mod query_where;
 mod query_order;
+mod query_distinct;
+mod query_window;
 mod query_group;
 mod query_token;
 mod query_table;
 mod query_set;
+mod query_conflict;
+mod query_subquery;
+mod query_with;
 mod query_select;
 mod query_return;
 mod query_limit_offset;
+mod query_lock;
 mod builder;
 /// internally just call format_query, can easily create your own
 pub mod op;
+/// typed expression accepted by every builder besides format_query
+pub mod expr;
+/// quoted table and column names, with a strict parse for names from the user
+pub mod ident;
+/// and / or / not tree given to where and join on as a single condition
+pub mod condition;
 mod query_field;
 mod static_constant;
 mod insert_builder;
 mod select_builder;
 mod update_builder;
 mod delete_builder;
-pub use query_token::{format_query};
+mod compound_builder;
+mod cursor;
+mod row;
+pub use query_token::{format_query, format_query_values, compose_query};
+pub use query_field::{ParameterValue, QueryWithParams};
+pub use query_subquery::{subquery, SubQuery};
+pub use query_with::WithQuery;
+pub use expr::Expr;
+pub use ident::{Ident, Alias};
+pub use query_order::{Order, Nulls, OrderWay};
+pub use query_limit_offset::{LimitOffset, RowCount};
+pub use query_lock::{Lock, LockStrength, LockWait};
+pub use condition::Condition;
 pub use builder::PostgresBuilder;
+pub use insert_builder::SqlInsert;
+pub use select_builder::SqlSelect;
+pub use update_builder::SqlUpdate;
+pub use delete_builder::SqlDelete;
+pub use compound_builder::{SqlCompound, CompoundOperator};
+pub use cursor::Cursor;
+pub use row::RowGet;
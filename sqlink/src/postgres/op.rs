@@ -1,17 +1,134 @@
-use crate::postgres::query_field::{ParameterValueAsRef};
-use crate::postgres::query_token::{format_query, TmpQueryTokens};
-pub fn eq<S: Into<String>>(field: S, arg: ParameterValueAsRef) -> (TmpQueryTokens, Vec<ParameterValueAsRef>)  {
-    format_query(format!("{} = {{}}", field.into()), vec![arg])
+use crate::postgres::query_field::{ParameterValue};
+use crate::postgres::query_token::{TmpQueryToken};
+use crate::postgres::query_token::{format_query_values, compose_query, TmpQueryTokens, FormatQueryTup};
+use crate::postgres::query_subquery::{subquery, SubQuery};
+pub fn eq<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} = {{}}", field.into()), vec![arg.into()])
 }
-pub fn lt<S: Into<String>>(field: S, arg: ParameterValueAsRef) -> (TmpQueryTokens, Vec<ParameterValueAsRef>)  {
-    format_query(format!("{} < {{}}", field.into()), vec![arg])
+pub fn lt<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} < {{}}", field.into()), vec![arg.into()])
 }
-pub fn lte<S: Into<String>>(field: S, arg: ParameterValueAsRef) -> (TmpQueryTokens, Vec<ParameterValueAsRef>)  {
-    format_query(format!("{} <= {{}}", field.into()), vec![arg])
+pub fn lte<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} <= {{}}", field.into()), vec![arg.into()])
 }
-pub fn gt<S: Into<String>>(field: S, arg: ParameterValueAsRef) -> (TmpQueryTokens, Vec<ParameterValueAsRef>)  {
-    format_query(format!("{} > {{}}", field.into()), vec![arg])
+pub fn gt<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} > {{}}", field.into()), vec![arg.into()])
 }
-pub fn gte<S: Into<String>>(field: S, arg: ParameterValueAsRef) -> (TmpQueryTokens, Vec<ParameterValueAsRef>)  {
-    format_query(format!("{} >= {{}}", field.into()), vec![arg])
+pub fn gte<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} >= {{}}", field.into()), vec![arg.into()])
+}
+pub fn ne<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} <> {{}}", field.into()), vec![arg.into()])
+}
+pub fn like<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} LIKE {{}}", field.into()), vec![arg.into()])
+}
+pub fn ilike<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} ILIKE {{}}", field.into()), vec![arg.into()])
+}
+pub fn is_null<'a, S: Into<String>>(field: S) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} IS NULL", field.into()), vec![])
+}
+pub fn is_not_null<'a, S: Into<String>>(field: S) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} IS NOT NULL", field.into()), vec![])
+}
+/// same as ne, but NULL is compared as a value
+pub fn is_distinct_from<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} IS DISTINCT FROM {{}}", field.into()), vec![arg.into()])
+}
+/// same as eq, but NULL is compared as a value
+pub fn is_not_distinct_from<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} IS NOT DISTINCT FROM {{}}", field.into()), vec![arg.into()])
+}
+pub fn between<'a, S: Into<String>, L: Into<ParameterValue<'a>>, H: Into<ParameterValue<'a>>>(field: S, low: L, high: H) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} BETWEEN {{}} AND {{}}", field.into()), vec![low.into(), high.into()])
+}
+fn in_list_query<'a, T>(field: String, keyword: &str, args: &'a [T], empty: &str) -> FormatQueryTup<'a> where T: postgres_types::ToSql + std::marker::Sync {
+    // IN () is a syntax error, an empty list never matches
+    if args.is_empty() {
+        return (TmpQueryTokens(vec![TmpQueryToken::Sql(empty.to_owned())]), Vec::new());
+    }
+    let placeholders = vec!["{}"; args.len()].join(",");
+    format_query_values(format!("{} {} ({})", field, keyword, placeholders), args.iter().map(ParameterValue::from).collect())
+}
+/// field IN ($1,$2,...), an empty slice becomes FALSE
+pub fn in_list<'a, S: Into<String>, T>(field: S, args: &'a [T]) -> FormatQueryTup<'a> where T: postgres_types::ToSql + std::marker::Sync {
+    in_list_query(field.into(), "IN", args, "FALSE")
+}
+/// field NOT IN ($1,$2,...), an empty slice becomes TRUE
+pub fn not_in<'a, S: Into<String>, T>(field: S, args: &'a [T]) -> FormatQueryTup<'a> where T: postgres_types::ToSql + std::marker::Sync {
+    in_list_query(field.into(), "NOT IN", args, "TRUE")
+}
+/// field = ANY($1), the argument is a single array parameter such as Vec<i32>
+pub fn eq_any<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} = ANY({{}})", field.into()), vec![arg.into()])
+}
+/// field <> ALL($1), the argument is a single array parameter such as Vec<i32>
+pub fn ne_all<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} <> ALL({{}})", field.into()), vec![arg.into()])
+}
+/// jsonb field @> $1
+pub fn json_contains<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} @> {{}}", field.into()), vec![arg.into()])
+}
+/// jsonb field ? $1, the key exists at the top level
+pub fn json_has_key<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, key: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} ? {{}}", field.into()), vec![key.into()])
+}
+/// jsonb field ->> $1 = $2, compare the key as text
+pub fn json_text_eq<'a, S: Into<String>, K: Into<ParameterValue<'a>>, P: Into<ParameterValue<'a>>>(field: S, key: K, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} ->> {{}} = {{}}", field.into()), vec![key.into(), arg.into()])
+}
+/// array field && $1, the arrays have any element in common
+pub fn array_overlap<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} && {{}}", field.into()), vec![arg.into()])
+}
+/// full text search, field is a tsvector such as to_tsvector('english', body) and the argument is plain text
+pub fn text_search<'a, S: Into<String>, P: Into<ParameterValue<'a>>>(field: S, arg: P) -> FormatQueryTup<'a> {
+    format_query_values(format!("{} @@ plainto_tsquery({{}})", field.into()), vec![arg.into()])
+}
+pub fn in_select<'a, S: Into<String>, T: Into<SubQuery<'a>>>(field: S, sub: T) -> FormatQueryTup<'a> {
+    compose_query(format!("{} IN {{}}", field.into()), vec![subquery(sub)])
+}
+pub fn not_in_select<'a, S: Into<String>, T: Into<SubQuery<'a>>>(field: S, sub: T) -> FormatQueryTup<'a> {
+    compose_query(format!("{} NOT IN {{}}", field.into()), vec![subquery(sub)])
+}
+pub fn exists<'a, T: Into<SubQuery<'a>>>(sub: T) -> FormatQueryTup<'a> {
+    compose_query("EXISTS {}", vec![subquery(sub)])
+}
+pub fn not_exists<'a, T: Into<SubQuery<'a>>>(sub: T) -> FormatQueryTup<'a> {
+    compose_query("NOT EXISTS {}", vec![subquery(sub)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::query_field::ParamIter;
+    fn build(ftup: FormatQueryTup) -> (String, usize) {
+        let built = (ftup.0).into_query_tokens(0).build(&mut ParamIter::default()).unwrap();
+        assert_eq!(built.parameters_loc.len(), ftup.1.len());
+        (built.query, ftup.1.len())
+    }
+    #[test]
+    fn test_op_in_list() {
+        let ids = vec![1, 2, 3];
+        assert_eq!(build(in_list("id", &ids)), ("id IN ($1,$2,$3)".to_owned(), 3));
+        assert_eq!(build(not_in("id", &ids[..1])), ("id NOT IN ($1)".to_owned(), 1));
+        let empty: Vec<i32> = Vec::new();
+        assert_eq!(build(in_list("id", &empty)), ("FALSE".to_owned(), 0));
+        assert_eq!(build(not_in("id", &empty)), ("TRUE".to_owned(), 0));
+    }
+    #[test]
+    fn test_op_condition() {
+        assert_eq!(build(ne("id", &1)).0, "id <> $1");
+        assert_eq!(build(between("age", &18, &65)), ("age BETWEEN $1 AND $2".to_owned(), 2));
+        assert_eq!(build(ilike("name", &"a%")).0, "name ILIKE $1");
+        assert_eq!(build(is_null("deleted_at")), ("deleted_at IS NULL".to_owned(), 0));
+        assert_eq!(build(is_distinct_from("parent_id", &1)).0, "parent_id IS DISTINCT FROM $1");
+        assert_eq!(build(eq_any("id", &vec![1, 2])), ("id = ANY($1)".to_owned(), 1));
+        assert_eq!(build(json_has_key("data", &"email")).0, "data ? $1");
+        assert_eq!(build(json_text_eq("data", &"email", &"a@b.c")), ("data ->> $1 = $2".to_owned(), 2));
+        assert_eq!(build(array_overlap("tags", &vec!["a"])).0, "tags && $1");
+        assert_eq!(build(text_search("to_tsvector(body)", &"rust")).0, "to_tsvector(body) @@ plainto_tsquery($1)");
+    }
 }
@@ -0,0 +1,120 @@
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_set::{QuerySets};
+use crate::postgres::query_where::{QueryWheres};
+use crate::postgres::ident::Ident;
+
+#[derive(Clone, Debug)]
+pub enum ConflictTarget {
+    Columns(Vec<Ident>),
+    Constraint(Ident),
+}
+
+impl From<&str> for ConflictTarget {
+    fn from(column: &str) -> Self {
+        ConflictTarget::Columns(vec![column.into()])
+    }
+}
+
+impl From<Ident> for ConflictTarget {
+    fn from(column: Ident) -> Self {
+        ConflictTarget::Columns(vec![column])
+    }
+}
+
+impl From<Vec<&str>> for ConflictTarget {
+    fn from(columns: Vec<&str>) -> Self {
+        ConflictTarget::Columns(columns.into_iter().map(|s| s.into()).collect())
+    }
+}
+
+impl From<Vec<Ident>> for ConflictTarget {
+    fn from(columns: Vec<Ident>) -> Self {
+        ConflictTarget::Columns(columns)
+    }
+}
+
+impl From<&[&str]> for ConflictTarget {
+    fn from(columns: &[&str]) -> Self {
+        ConflictTarget::Columns(columns.iter().map(|s| (*s).into()).collect())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ConflictAction {
+    Nothing,
+    Update,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryConflict {
+    pub target: Option<ConflictTarget>,
+    pub target_wheres: QueryWheres, // partial unique index predicate
+    pub action: Option<ConflictAction>,
+    pub sets: QuerySets,
+    pub wheres: QueryWheres,
+}
+
+impl QueryConflict {
+    pub fn parameter_len(&self) -> usize {
+        self.target_wheres.parameter_len() + self.sets.parameter_len() + self.wheres.parameter_len()
+    }
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        v.push("ON CONFLICT".to_owned());
+        match &self.target {
+            Some(ConflictTarget::Columns(columns)) => {
+                if columns.is_empty() {
+                    return Err(Error::EmptyColumns { context: ErrorContext::default() });
+                }
+                let escaped: Vec<String> = columns.iter().map(|c| c.to_sql()).collect();
+                v.push(format!("({})", escaped.join(",")));
+                let built = self.target_wheres.build(i)?;
+                if !built.query.is_empty() {
+                    v.push(format!("WHERE {}", built.query));
+                    p.extend(built.parameters_loc);
+                }
+            },
+            Some(ConflictTarget::Constraint(name)) => {
+                if self.target_wheres.len() > 0 {
+                    return Err(Error::ConstraintWithWhere { context: ErrorContext::identifier(name.display_name()) });
+                }
+                v.push(format!("ON CONSTRAINT {}", name.to_sql()));
+            },
+            None => {
+                if self.target_wheres.len() > 0 {
+                    return Err(Error::ConflictWithoutTarget { context: ErrorContext::default() });
+                }
+            },
+        }
+        match &self.action {
+            None => {
+                return Err(Error::ConflictWithoutAction { context: ErrorContext::default() });
+            },
+            Some(ConflictAction::Nothing) => {
+                v.push("DO NOTHING".to_owned());
+            },
+            Some(ConflictAction::Update) => {
+                if self.target.is_none() {
+                    return Err(Error::ConflictWithoutTarget { context: ErrorContext::default() });
+                }
+                if self.sets.len() == 0 {
+                    return Err(Error::EmptySet { context: ErrorContext::default() });
+                }
+                let built_for_update = self.sets.build_for_update(i)?;
+                v.push(format!("DO UPDATE SET {}", built_for_update.query));
+                p.extend(built_for_update.parameters_loc);
+                let built = self.wheres.build(i)?;
+                if !built.query.is_empty() {
+                    v.push(format!("WHERE {}", built.query));
+                    p.extend(built.parameters_loc);
+                }
+            },
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(" "),
+            parameters_loc: p,
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_order::QueryOrders;
+use crate::postgres::query_token::QueryTokens;
+
+// fold the identifiers the way postgres does, so col("user_id") and "user_id" compare equal:
+// a quoted identifier is unquoted and the other identifiers are lowercased, string literals are kept as they are
+fn normalize(shape: &str) -> String {
+    let mut normalized = String::with_capacity(shape.len());
+    let mut chars = shape.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                // a literal keeps its quotes, an identifier does not, a doubled quote is an escaped quote
+                let literal = c == '\'';
+                if literal {
+                    normalized.push(c);
+                }
+                while let Some(inner) = chars.next() {
+                    if inner == c && chars.next_if_eq(&c).is_none() {
+                        break;
+                    }
+                    if inner == c && literal {
+                        normalized.push(c);
+                    }
+                    normalized.push(inner);
+                }
+                if literal {
+                    normalized.push(c);
+                }
+            },
+            c => normalized.extend(c.to_lowercase()),
+        }
+    }
+    normalized
+}
+
+#[derive(Clone, Debug)]
+pub enum QueryDistinct {
+    All,
+    On(Vec<QueryTokens>),
+}
+
+impl QueryDistinct {
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        match self {
+            QueryDistinct::All => Ok(QueryWithParamsLoc {
+                query: "DISTINCT".to_owned(),
+                parameters_loc: Vec::new(),
+            }),
+            QueryDistinct::On(fields) => {
+                if fields.is_empty() {
+                    return Err(Error::EmptyColumns {
+                        context: ErrorContext::identifier("DISTINCT ON"),
+                    });
+                }
+                let mut v: Vec<String> = Vec::new();
+                let mut p: Vec<usize> = Vec::new();
+                for field in fields {
+                    let built = field.build(i)?;
+                    v.push(built.query);
+                    p.extend(built.parameters_loc);
+                }
+                Ok(QueryWithParamsLoc {
+                    query: format!("DISTINCT ON ({})", v.join(", ")),
+                    parameters_loc: p,
+                })
+            },
+        }
+    }
+    /// same rule as postgres, the leading ORDER BY expressions have to be DISTINCT ON expressions,
+    /// in any order, so the first row of each distinct group is well defined
+    pub fn check_order(&self, orders: &QueryOrders) -> Result<(), Error> {
+        if let QueryDistinct::On(fields) = self {
+            let distinct: Vec<String> = fields.iter().map(|f| normalize(&f.shape())).collect();
+            for order in orders.names().take(fields.len()) {
+                let shape = order.shape();
+                if !distinct.contains(&normalize(&shape)) {
+                    return Err(Error::DistinctOnMismatch {
+                        context: ErrorContext::identifier(shape),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::query_order::QueryOrder;
+    fn order(name: &str, order_way: &str) -> QueryOrder {
+        QueryOrder::new(name.into(), order_way)
+    }
+    #[test]
+    fn test_distinct_on_order() {
+        let distinct = QueryDistinct::On(vec!["user_id".into(), "status".into()]);
+        assert_eq!(distinct.build(&mut ParamIter::default()).unwrap().query, "DISTINCT ON (user_id, status)");
+        let mut orders = QueryOrders::default();
+        orders.push(order("status", "ASC"));
+        orders.push(order("user_id", "DESC"));
+        orders.push(order("created_at", "DESC"));
+        assert!(distinct.check_order(&orders).is_ok());
+        let mut orders = QueryOrders::default();
+        orders.push(order("user_id", "ASC"));
+        orders.push(order("created_at", "DESC"));
+        let error = distinct.check_order(&orders).unwrap_err();
+        assert!(matches!(error, Error::DistinctOnMismatch { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("created_at"));
+        assert!(distinct.check_order(&QueryOrders::default()).is_ok());
+        assert!(QueryDistinct::All.check_order(&orders).is_ok());
+    }
+    #[test]
+    fn test_distinct_on_order_quoted() {
+        use crate::postgres::expr::col;
+        let (tokens, _) = col("u.user_id").into_format_query();
+        let distinct = QueryDistinct::On(vec![tokens.into_query_tokens(0), "LOWER(Name)".into(), "\"Status\"".into()]);
+        let mut orders = QueryOrders::default();
+        orders.push(order("U.user_id", "ASC"));
+        orders.push(order("lower(\"name\")", "ASC"));
+        orders.push(order("\"Status\"", "ASC"));
+        assert!(distinct.check_order(&orders).is_ok());
+        let mut orders = QueryOrders::default();
+        orders.push(order("status", "ASC"));
+        assert!(matches!(distinct.check_order(&orders), Err(Error::DistinctOnMismatch { .. })));
+        assert_eq!(normalize("a = 'It''s' AND \"B\"\"c\" = Ab"), "a = 'It''s' and B\"c = ab");
+    }
+}
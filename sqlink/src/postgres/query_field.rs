@@ -1,9 +1,121 @@
+use std::fmt;
+use std::sync::Arc;
+use bytes::BytesMut;
+use postgres_types::{ToSql, Type, IsNull};
+use crate::error::{Error, ErrorContext};
+use crate::postgres::static_constant::{PARAM_NOTATION, MAX_PARAMETER};
+
 pub type ParameterValueAsRef<'a> = &'a (dyn postgres_types::ToSql + std::marker::Sync);
+/// a parameter that is either borrowed by the builder or owned by it,
+/// a builder with only owned parameters is 'static and can be sent across threads
+#[derive(Clone)]
+pub enum ParameterValue<'a> {
+    Borrowed(ParameterValueAsRef<'a>),
+    Owned(Arc<dyn ToSql + Sync + Send>),
+}
+impl<'a> ParameterValue<'a> {
+    pub fn owned<T>(param: T) -> ParameterValue<'static> where T: ToSql + Sync + Send + 'static {
+        ParameterValue::Owned(Arc::new(param))
+    }
+    pub fn as_ref(&self) -> ParameterValueAsRef<'_> {
+        match self {
+            ParameterValue::Borrowed(param) => *param,
+            ParameterValue::Owned(param) => param.as_ref(),
+        }
+    }
+}
+impl<'a, T> From<&'a T> for ParameterValue<'a> where T: ToSql + Sync + 'a {
+    fn from(param: &'a T) -> Self {
+        ParameterValue::Borrowed(param)
+    }
+}
+impl<'a> From<ParameterValueAsRef<'a>> for ParameterValue<'a> {
+    fn from(param: ParameterValueAsRef<'a>) -> Self {
+        ParameterValue::Borrowed(param)
+    }
+}
+impl<'a> fmt::Debug for ParameterValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_ref().fmt(f)
+    }
+}
+// the type check is done by the inner value in to_sql_checked, so accepts is always true here
+impl<'a> ToSql for ParameterValue<'a> {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        self.as_ref().to_sql_checked(ty, out)
+    }
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+    fn to_sql_checked(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        self.as_ref().to_sql_checked(ty, out)
+    }
+}
+#[derive(Debug)]
 pub struct QueryWithParamsLoc {
     pub query: String,
     pub parameters_loc: Vec<usize>,
 }
+#[derive(Clone, Debug)]
 pub struct QueryWithParams<'a> {
     pub query: String,
-    pub parameters: Vec<ParameterValueAsRef<'a>>,
+    pub parameters: Vec<ParameterValue<'a>>,
+}
+impl<'a> QueryWithParams<'a> {
+    /// the parameters as the slice expected by postgres query / execute
+    pub fn params(&self) -> Vec<ParameterValueAsRef<'_>> {
+        self.parameters.iter().map(|param| param.as_ref()).collect()
+    }
+}
+/// the $n counter while building a statement, postgres support up to 65535 parameters
+#[derive(Debug)]
+pub struct ParamIter(usize);
+impl ParamIter {
+    pub fn next_placeholder(&mut self) -> Result<String, Error> {
+        if self.0 > MAX_PARAMETER {
+            return Err(Error::ParameterOverflow {
+                max: MAX_PARAMETER,
+                context: ErrorContext::default(),
+            });
+        }
+        let placeholder = format!("{}{}", PARAM_NOTATION, self.0);
+        self.0 += 1;
+        Ok(placeholder)
+    }
+}
+impl Default for ParamIter {
+    fn default() -> Self {
+        ParamIter(1)
+    }
+}
+impl From<usize> for ParamIter {
+    fn from(start: usize) -> Self {
+        ParamIter(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_param_iter() {
+        let mut param_iter = ParamIter::from(127);
+        assert_eq!(param_iter.next_placeholder().unwrap(), "$127");
+        assert_eq!(param_iter.next_placeholder().unwrap(), "$128");
+        let mut param_iter = ParamIter::from(MAX_PARAMETER);
+        assert_eq!(param_iter.next_placeholder().unwrap(), "$65535");
+        assert!(matches!(param_iter.next_placeholder(), Err(Error::ParameterOverflow { max: 65535, .. })));
+    }
+    #[test]
+    fn test_parameter_value_type_check() {
+        let mut out = BytesMut::new();
+        let owned = ParameterValue::owned(1i32);
+        assert!(owned.to_sql_checked(&Type::INT4, &mut out).is_ok());
+        assert!(owned.to_sql_checked(&Type::TEXT, &mut out).is_err());
+        let name = "foo";
+        let borrowed = ParameterValue::from(&name);
+        assert!(borrowed.to_sql_checked(&Type::TEXT, &mut out).is_ok());
+        assert!(borrowed.to_sql_checked(&Type::INT4, &mut out).is_err());
+        assert_eq!(format!("{:?}", vec![owned, borrowed]), "[1, \"foo\"]");
+    }
 }
@@ -1,17 +1,23 @@
 use crate::error::Error;
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_token::QueryTokens;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct QueryGroups(Vec<QueryGroup>);
 
-impl Default for QueryGroups {
-    fn default() -> Self {
-        QueryGroups(Vec::new())
-    }
-}
-
 impl QueryGroups {
-    pub fn build(&self) -> Result<String, Error> {
-        Ok(self.0.clone().into_iter().map(|s| s.build()).collect::<Vec<String>>().join(", "))
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for field in &self.0 {
+            let built = field.name.build(i)?;
+            v.push(built.query);
+            p.extend(built.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(", "),
+            parameters_loc: p,
+        })
     }
     pub fn push(&mut self, field: QueryGroup) {
         self.0.push(field);
@@ -23,13 +29,7 @@ impl QueryGroups {
 
 #[derive(Clone, Debug)]
 pub struct QueryGroup {
-    pub name: String,
-}
-
-impl QueryGroup {
-    fn build(&self) -> String {
-        self.name.clone()
-    }
+    pub name: QueryTokens,
 }
 
 #[cfg(test)]
@@ -37,10 +37,10 @@ mod tests {
     use super::*;
     #[test]
     fn test_group_1() {
-        let field: QueryGroup = QueryGroup {
-            name: "u.id".to_owned(),
-        };
-
-        assert_eq!(field.build(), "u.id");
+        let mut groups = QueryGroups::default();
+        groups.push(QueryGroup {
+            name: "u.id".into(),
+        });
+        assert_eq!(groups.build(&mut ParamIter::default()).unwrap().query, "u.id");
     }
 }
@@ -1,61 +1,209 @@
-use crate::error::Error;
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter, ParameterValue};
+use crate::postgres::query_token::{QueryTokens, QueryToken};
 
+/// a row count of LIMIT / OFFSET / FETCH FIRST
 #[derive(Clone, Debug)]
-pub struct QueryLimitOffset {
-    limit: usize,
-    offset: usize,
+pub enum RowCount<'a> {
+    /// written into the query
+    Value(usize),
+    /// LIMIT ALL, the same as no limit
+    All,
+    /// a bind parameter, so statements with different counts share the same prepared statement,
+    /// postgres expects a bigint so give an i64
+    Param(ParameterValue<'a>),
 }
 
-impl QueryLimitOffset {
-    pub fn build(&self) -> Result<String, Error> {
-        if self.offset == 0 {
-            Ok(format!("LIMIT {}", self.limit))
-        } else {
-            Ok(format!("LIMIT {} OFFSET {}", self.limit, self.offset))
-        }
+impl<'a> From<usize> for RowCount<'a> {
+    fn from(count: usize) -> Self {
+        RowCount::Value(count)
+    }
+}
+impl<'a> From<ParameterValue<'a>> for RowCount<'a> {
+    fn from(param: ParameterValue<'a>) -> Self {
+        RowCount::Param(param)
+    }
+}
+impl<'a> From<&'a i64> for RowCount<'a> {
+    fn from(count: &'a i64) -> Self {
+        RowCount::Param(count.into())
     }
 }
 
-impl From<usize> for QueryLimitOffset {
-    fn from(limit: usize) -> Self {
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Fetch {
+    Only,
+    WithTies,
+}
+
+/// LIMIT m OFFSET n, or OFFSET n ROWS FETCH FIRST m ROWS ONLY / WITH TIES
+///
+/// limit_offset(10) and limit_offset((10, 20)) still work, this is for the other forms
+#[derive(Clone, Debug, Default)]
+pub struct LimitOffset<'a> {
+    limit: Option<RowCount<'a>>,
+    offset: Option<RowCount<'a>>,
+    fetch: Option<Fetch>,
+}
+
+impl<'a> LimitOffset<'a> {
+    pub fn new() -> Self {
+        LimitOffset::default()
+    }
+    pub fn limit<C: Into<RowCount<'a>>>(mut self, count: C) -> Self {
+        self.limit = Some(count.into());
+        self
+    }
+    pub fn limit_all(self) -> Self {
+        self.limit(RowCount::All)
+    }
+    /// without a limit it is only OFFSET n
+    pub fn offset<C: Into<RowCount<'a>>>(mut self, count: C) -> Self {
+        self.offset = Some(count.into());
+        self
+    }
+    /// FETCH FIRST m ROWS ONLY, the offset is written as OFFSET n ROWS
+    pub fn fetch_first<C: Into<RowCount<'a>>>(mut self, count: C) -> Self {
+        self.fetch = Some(Fetch::Only);
+        self.limit(count)
+    }
+    /// FETCH FIRST m ROWS WITH TIES, the rows that tie with the last row are also returned, requires ORDER BY
+    pub fn with_ties(mut self) -> Self {
+        self.fetch = Some(Fetch::WithTies);
+        self
+    }
+    fn count_tokens(count: RowCount<'a>, parameters: &mut Vec<ParameterValue<'a>>) -> QueryTokens {
+        match count {
+            RowCount::Value(count) => QueryTokens(vec![QueryToken::Sql(count.to_string())]),
+            RowCount::All => QueryTokens(vec![QueryToken::Sql("ALL".to_owned())]),
+            RowCount::Param(param) => {
+                parameters.push(param);
+                QueryTokens(vec![QueryToken::ParameterLoc(parameters.len() - 1)])
+            },
+        }
+    }
+    /// move the bind parameters into the parameters of the builder
+    pub(crate) fn into_query(self, parameters: &mut Vec<ParameterValue<'a>>) -> QueryLimitOffset {
+        let limit = match (self.fetch, self.limit) {
+            (Some(_), Some(RowCount::All)) => Some(QueryTokens(vec![QueryToken::Error(Error::Syntax {
+                message: "FETCH FIRST cannot be ALL".to_owned(),
+                context: ErrorContext::default(),
+            })])),
+            (_, limit) => limit.map(|count| LimitOffset::count_tokens(count, parameters)),
+        };
         QueryLimitOffset {
-            limit: limit,
-            offset: 0,
+            limit,
+            offset: self.offset.map(|count| LimitOffset::count_tokens(count, parameters)),
+            fetch: self.fetch,
         }
     }
 }
 
-impl From<(usize, usize)> for QueryLimitOffset {
+impl<'a> From<usize> for LimitOffset<'a> {
+    fn from(limit: usize) -> Self {
+        LimitOffset::new().limit(limit)
+    }
+}
+
+impl<'a> From<(usize, usize)> for LimitOffset<'a> {
     fn from(tup: (usize, usize)) -> Self {
-        QueryLimitOffset {
-            limit: tup.0,
-            offset: tup.1,
+        let limit_offset = LimitOffset::new().limit(tup.0);
+        if tup.1 == 0 {
+            limit_offset
+        } else {
+            limit_offset.offset(tup.1)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryLimitOffset {
+    limit: Option<QueryTokens>,
+    offset: Option<QueryTokens>,
+    fetch: Option<Fetch>,
+}
+
+impl QueryLimitOffset {
+    pub fn with_ties(&self) -> bool {
+        self.fetch == Some(Fetch::WithTies)
+    }
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        // the parameters are numbered in the order they are written
+        let build_limit = |i: &mut ParamIter| match &self.limit {
+            Some(limit) => limit.build(i).map(Some),
+            None => Ok(None),
+        };
+        if self.fetch.is_none() {
+            if let Some(built) = build_limit(i)? {
+                v.push(format!("LIMIT {}", built.query));
+                p.extend(built.parameters_loc);
+            }
         }
+        if let Some(offset) = &self.offset {
+            let built = offset.build(i)?;
+            match self.fetch {
+                Some(_) => v.push(format!("OFFSET {} ROWS", built.query)),
+                None => v.push(format!("OFFSET {}", built.query)),
+            }
+            p.extend(built.parameters_loc);
+        }
+        if let Some(fetch) = self.fetch {
+            let built = build_limit(i)?.ok_or_else(|| Error::Syntax {
+                message: "WITH TIES requires FETCH FIRST".to_owned(),
+                context: ErrorContext::default(),
+            })?;
+            match fetch {
+                Fetch::Only => v.push(format!("FETCH FIRST {} ROWS ONLY", built.query)),
+                Fetch::WithTies => v.push(format!("FETCH FIRST {} ROWS WITH TIES", built.query)),
+            }
+            p.extend(built.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(" "),
+            parameters_loc: p,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    fn build(limit_offset: LimitOffset) -> Result<(String, usize), Error> {
+        let mut parameters = Vec::new();
+        let built = limit_offset.into_query(&mut parameters).build(&mut ParamIter::default())?;
+        assert_eq!(built.parameters_loc.len(), parameters.len());
+        Ok((built.query, parameters.len()))
+    }
+
     #[test]
     fn test_limit_offset_1() {
-        let limit_offset: QueryLimitOffset = QueryLimitOffset {
-            limit: 10,
-            offset: 0,
-        };
-
-        assert_eq!(limit_offset.build().unwrap(), "LIMIT 10");
+        let limit_offset = LimitOffset::new().limit(10);
+        assert_eq!(build(limit_offset).unwrap().0, "LIMIT 10");
     }
 
     #[test]
     fn test_limit_offset_2() {
-        let limit_offset: QueryLimitOffset = (1usize).into();
-        assert_eq!(limit_offset.build().unwrap(), "LIMIT 1");
+        let limit_offset: LimitOffset = (1usize).into();
+        assert_eq!(build(limit_offset).unwrap().0, "LIMIT 1");
     }
 
     #[test]
     fn test_limit_offset_3() {
-        let limit_offset: QueryLimitOffset = (10, 10).into();
-        assert_eq!(limit_offset.build().unwrap(), "LIMIT 10 OFFSET 10");
+        let limit_offset: LimitOffset = (10, 10).into();
+        assert_eq!(build(limit_offset).unwrap().0, "LIMIT 10 OFFSET 10");
+    }
+
+    #[test]
+    fn test_limit_offset_forms() {
+        let (limit, offset) = (10i64, 20i64);
+        assert_eq!(build(LimitOffset::new().limit(&limit).offset(&offset)).unwrap(), ("LIMIT $1 OFFSET $2".to_owned(), 2));
+        assert_eq!(build(LimitOffset::new().limit_all().offset(20)).unwrap().0, "LIMIT ALL OFFSET 20");
+        assert_eq!(build(LimitOffset::new().offset(&offset)).unwrap(), ("OFFSET $1".to_owned(), 1));
+        assert_eq!(build(LimitOffset::new().offset(20).fetch_first(&limit)).unwrap(), ("OFFSET 20 ROWS FETCH FIRST $1 ROWS ONLY".to_owned(), 1));
+        assert_eq!(build(LimitOffset::new().fetch_first(5).with_ties()).unwrap().0, "FETCH FIRST 5 ROWS WITH TIES");
+        assert!(matches!(build(LimitOffset::new().fetch_first(RowCount::All)), Err(Error::Syntax { .. })));
+        assert!(matches!(build(LimitOffset::new().offset(5).with_ties()), Err(Error::Syntax { .. })));
     }
 }
@@ -0,0 +1,128 @@
+use crate::error::{Error, ErrorContext};
+use crate::postgres::ident::Alias;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockStrength {
+    Update,
+    NoKeyUpdate,
+    Share,
+    KeyShare,
+}
+
+/// what to do with a row that is already locked, waiting is the default
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockWait {
+    Wait,
+    NoWait,
+    SkipLocked,
+}
+
+/// FOR UPDATE [OF table] [NOWAIT | SKIP LOCKED], given to SqlSelect::lock
+///
+/// Lock::update().skip_locked() is the usual job queue, a select can have more than 1 lock for different tables
+#[derive(Clone, Debug)]
+pub struct Lock {
+    strength: LockStrength,
+    of: Vec<Alias>,
+    wait: LockWait,
+}
+
+impl Lock {
+    pub fn new(strength: LockStrength) -> Self {
+        Lock {
+            strength,
+            of: Vec::new(),
+            wait: LockWait::Wait,
+        }
+    }
+    pub fn update() -> Self {
+        Lock::new(LockStrength::Update)
+    }
+    pub fn no_key_update() -> Self {
+        Lock::new(LockStrength::NoKeyUpdate)
+    }
+    pub fn share() -> Self {
+        Lock::new(LockStrength::Share)
+    }
+    pub fn key_share() -> Self {
+        Lock::new(LockStrength::KeyShare)
+    }
+    /// only lock the rows of this table, use the alias when the table has one.
+    /// OF takes the name as it is written in FROM without the schema, a name with "." fails the build.
+    /// a string is written as is like an alias, give an Ident to quote it
+    pub fn of<S: Into<Alias>>(mut self, table: S) -> Self {
+        self.of.push(table.into());
+        self
+    }
+    pub fn nowait(mut self) -> Self {
+        self.wait = LockWait::NoWait;
+        self
+    }
+    pub fn skip_locked(mut self) -> Self {
+        self.wait = LockWait::SkipLocked;
+        self
+    }
+    fn to_sql(&self) -> Result<String, Error> {
+        let mut v: Vec<String> = Vec::new();
+        v.push(match self.strength {
+            LockStrength::Update => "FOR UPDATE".to_owned(),
+            LockStrength::NoKeyUpdate => "FOR NO KEY UPDATE".to_owned(),
+            LockStrength::Share => "FOR SHARE".to_owned(),
+            LockStrength::KeyShare => "FOR KEY SHARE".to_owned(),
+        });
+        if !self.of.is_empty() {
+            if let Some(table) = self.of.iter().find(|t| t.is_qualified()) {
+                return Err(Error::Syntax {
+                    message: "OF takes a table name or alias without a schema".to_owned(),
+                    context: ErrorContext::identifier(table.display_name()),
+                });
+            }
+            let tables: Vec<String> = self.of.iter().map(|t| t.to_sql()).collect();
+            v.push(format!("OF {}", tables.join(", ")));
+        }
+        match self.wait {
+            LockWait::Wait => {},
+            LockWait::NoWait => v.push("NOWAIT".to_owned()),
+            LockWait::SkipLocked => v.push("SKIP LOCKED".to_owned()),
+        }
+        Ok(v.join(" "))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryLocks(Vec<Lock>);
+
+impl QueryLocks {
+    pub fn build(&self) -> Result<String, Error> {
+        let locks = self.0.iter().map(|lock| lock.to_sql()).collect::<Result<Vec<String>, Error>>()?;
+        Ok(locks.join(" "))
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn push(&mut self, lock: Lock) {
+        self.0.push(lock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::ident::Ident;
+    #[test]
+    fn test_lock() {
+        let mut locks = QueryLocks::default();
+        locks.push(Lock::update().skip_locked());
+        assert_eq!(locks.build().unwrap(), "FOR UPDATE SKIP LOCKED");
+        locks.push(Lock::key_share().of("u").of(Ident::new("Team")).nowait());
+        assert_eq!(locks.build().unwrap(), "FOR UPDATE SKIP LOCKED FOR KEY SHARE OF u, \"Team\" NOWAIT");
+        assert_eq!(Lock::no_key_update().to_sql().unwrap(), "FOR NO KEY UPDATE");
+        assert_eq!(Lock::share().to_sql().unwrap(), "FOR SHARE");
+        locks.push(Lock::update().of("public.team"));
+        let error = locks.build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("public.team"));
+        let error = Lock::update().of(Ident::new("public.team")).to_sql().unwrap_err();
+        assert_eq!(error.context().identifier.as_deref(), Some("public.team"));
+    }
+}
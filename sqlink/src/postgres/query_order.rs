@@ -1,17 +1,147 @@
-use crate::error::Error;
+use std::convert::{TryFrom, TryInto};
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_token::{QueryTokens, QueryToken};
 
-#[derive(Clone, Debug)]
-pub struct QueryOrders(Vec<QueryOrder>);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
 
-impl Default for QueryOrders {
-    fn default() -> Self {
-        QueryOrders(Vec::new())
+impl Order {
+    pub fn nulls_first(self) -> OrderWay {
+        OrderWay {
+            order: Some(self),
+            nulls: Some(Nulls::First),
+        }
+    }
+    pub fn nulls_last(self) -> OrderWay {
+        OrderWay {
+            order: Some(self),
+            nulls: Some(Nulls::Last),
+        }
+    }
+    /// the opposite direction
+    pub fn reverse(self) -> Order {
+        match self {
+            Order::Asc => Order::Desc,
+            Order::Desc => Order::Asc,
+        }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+/// the direction after an ORDER BY expression, none of them means the postgres default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OrderWay {
+    pub order: Option<Order>,
+    pub nulls: Option<Nulls>,
+}
+
+impl OrderWay {
+    pub fn to_sql(&self) -> String {
+        let mut v: Vec<&str> = Vec::new();
+        match self.order {
+            Some(Order::Asc) => v.push("ASC"),
+            Some(Order::Desc) => v.push("DESC"),
+            None => (),
+        }
+        match self.nulls {
+            Some(Nulls::First) => v.push("NULLS FIRST"),
+            Some(Nulls::Last) => v.push("NULLS LAST"),
+            None => (),
+        }
+        v.join(" ")
+    }
+}
+
+impl From<Order> for OrderWay {
+    fn from(order: Order) -> Self {
+        OrderWay {
+            order: Some(order),
+            nulls: None,
+        }
+    }
+}
+
+impl From<Nulls> for OrderWay {
+    fn from(nulls: Nulls) -> Self {
+        OrderWay {
+            order: None,
+            nulls: Some(nulls),
+        }
+    }
+}
+
+impl From<(Order, Nulls)> for OrderWay {
+    fn from(tup: (Order, Nulls)) -> Self {
+        OrderWay {
+            order: Some(tup.0),
+            nulls: Some(tup.1),
+        }
+    }
+}
+
+/// a direction from a string, such as the sort of a request, only ASC / DESC and NULLS FIRST / NULLS LAST are accepted
+impl TryFrom<&str> for OrderWay {
+    type Error = Error;
+    fn try_from(order_way: &str) -> Result<Self, Error> {
+        let words: Vec<String> = order_way.split_whitespace().map(|w| w.to_uppercase()).collect();
+        let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+        let (order, rest) = match words.split_first() {
+            Some((&"ASC", rest)) => (Some(Order::Asc), rest),
+            Some((&"DESC", rest)) => (Some(Order::Desc), rest),
+            _ => (None, &words[..]),
+        };
+        let nulls = match rest {
+            [] => None,
+            ["NULLS", "FIRST"] => Some(Nulls::First),
+            ["NULLS", "LAST"] => Some(Nulls::Last),
+            _ => return Err(Error::InvalidOrder {
+                context: ErrorContext::identifier(order_way),
+            }),
+        };
+        Ok(OrderWay {
+            order,
+            nulls,
+        })
+    }
+}
+
+impl TryFrom<String> for OrderWay {
+    type Error = Error;
+    fn try_from(order_way: String) -> Result<Self, Error> {
+        OrderWay::try_from(order_way.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryOrders(Vec<QueryOrder>);
+
 impl QueryOrders {
-    pub fn build(&self) -> Result<String, Error> {
-        Ok(self.0.clone().into_iter().map(|s| s.build()).collect::<Vec<String>>().join(", "))
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for field in &self.0 {
+            let built = field.name.build(i)?;
+            let order_way = field.order_way.to_sql();
+            if order_way.is_empty() {
+                v.push(built.query);
+            } else {
+                v.push(format!("{} {}", built.query, order_way));
+            }
+            p.extend(built.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(", "),
+            parameters_loc: p,
+        })
     }
     pub fn push(&mut self, field: QueryOrder) {
         self.0.push(field);
@@ -19,16 +149,32 @@ impl QueryOrders {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    /// the ordered expressions without the direction
+    pub fn names(&self) -> impl Iterator<Item = &QueryTokens> {
+        self.0.iter().map(|field| &field.name)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct QueryOrder {
-    pub name: String, // TBD on DESC ASC NULL FIRST CASE WHEN
+    pub name: QueryTokens,
+    pub order_way: OrderWay,
 }
 
 impl QueryOrder {
-    fn build(&self) -> String {
-        self.name.clone()
+    /// an invalid direction is kept as an error token, so it is returned on build
+    pub fn new<O>(mut name: QueryTokens, order_way: O) -> QueryOrder where O: TryInto<OrderWay>, O::Error: Into<Error> {
+        let order_way = match order_way.try_into() {
+            Ok(order_way) => order_way,
+            Err(e) => {
+                name.push(QueryToken::Error(e.into()));
+                OrderWay::default()
+            },
+        };
+        QueryOrder {
+            name,
+            order_way,
+        }
     }
 }
 
@@ -37,10 +183,31 @@ mod tests {
     use super::*;
     #[test]
     fn test_order_1() {
-        let field: QueryOrder = QueryOrder {
-            name: "u.id ASC".to_owned(),
-        };
-
-        assert_eq!(field.build(), "u.id ASC");
+        let mut orders = QueryOrders::default();
+        orders.push(QueryOrder {
+            name: "u.id".into(),
+            order_way: Order::Asc.into(),
+        });
+        orders.push(QueryOrder {
+            name: "u.name".into(),
+            order_way: Order::Desc.nulls_last(),
+        });
+        assert_eq!(orders.build(&mut ParamIter::default()).unwrap().query, "u.id ASC, u.name DESC NULLS LAST");
+    }
+    #[test]
+    fn test_order_way_from_str() {
+        assert_eq!(OrderWay::try_from("desc").unwrap(), Order::Desc.into());
+        assert_eq!(OrderWay::try_from(" ASC  nulls first ").unwrap(), Order::Asc.nulls_first());
+        assert_eq!(OrderWay::try_from("NULLS LAST").unwrap(), Nulls::Last.into());
+        assert_eq!(OrderWay::try_from("").unwrap(), OrderWay::default());
+        for invalid in &["DESC; DROP TABLE user", "DOWN", "ASC DESC", "NULLS", "ASC NULLS FIRST LAST"] {
+            let error = OrderWay::try_from(*invalid).unwrap_err();
+            assert!(matches!(error, Error::InvalidOrder { .. }));
+            assert_eq!(error.context().identifier.as_deref(), Some(*invalid));
+        }
+        let order = QueryOrder::new("u.id".into(), "sideways");
+        let mut orders = QueryOrders::default();
+        orders.push(order);
+        assert!(matches!(orders.build(&mut ParamIter::default()), Err(Error::InvalidOrder { .. })));
     }
 }
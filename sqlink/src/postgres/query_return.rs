@@ -1,3 +1,2 @@
-use crate::postgres::query_select::{QuerySelects, QuerySelectField};
+use crate::postgres::query_select::QuerySelects;
 pub type QueryReturns = QuerySelects;
-pub type QueryReturnField = QuerySelectField;
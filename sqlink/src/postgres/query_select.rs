@@ -1,38 +1,70 @@
 use crate::error::Error;
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter, ParameterValue};
+use crate::postgres::query_token::{QueryTokens};
+use crate::postgres::expr::Expr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct QuerySelects(Vec<QuerySelectField>);
 
-impl Default for QuerySelects {
-    fn default() -> Self {
-        QuerySelects(Vec::new())
-    }
-}
-
 impl QuerySelects {
-    pub fn build(&self) -> Result<String, Error> {
-        Ok(self.0.clone().into_iter().map(|s| s.build()).collect::<Vec<String>>().join(", "))
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for field in &self.0 {
+            let built = field.build(i)?;
+            v.push(built.query);
+            p.extend(built.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(", "),
+            parameters_loc: p,
+        })
     }
     pub fn push(&mut self, field: QuerySelectField) {
         self.0.push(field);
     }
+    /// the parameters of the expression are moved into the parameters of the statement
+    pub fn push_expr<'a>(&mut self, expr: Expr<'a>, alias: Option<String>, parameters: &mut Vec<ParameterValue<'a>>) {
+        let len = parameters.len();
+        let (tokens, expr_parameters) = expr.into_format_query();
+        parameters.extend(expr_parameters);
+        self.0.push(QuerySelectField {
+            name: tokens.into_query_tokens(len),
+            alias,
+        });
+    }
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    pub fn parameter_len(&self) -> usize {
+        self.0.iter().map(|field| field.name.parameter_len()).sum()
+    }
+    pub fn column_count(&self) -> Option<usize> {
+        let star = self.0.iter().any(|field| field.alias.is_none() && field.name.shape().ends_with('*'));
+        if star {
+            None
+        } else {
+            Some(self.0.len())
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct QuerySelectField {
-    pub name: String,
+    pub name: QueryTokens,
     pub alias: Option<String>,
 }
 
 impl QuerySelectField {
-    fn build(&self) -> String {
+    fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let built = self.name.build(i)?;
         if let Some(alias) = &self.alias {
-            format!("{} AS {}", self.name, alias)
+            Ok(QueryWithParamsLoc {
+                query: format!("{} AS {}", built.query, alias),
+                parameters_loc: built.parameters_loc,
+            })
         } else {
-            self.name.clone()
+            Ok(built)
         }
     }
 }
@@ -40,7 +72,7 @@ impl QuerySelectField {
 impl From<&str> for QuerySelectField {
     fn from(field: &str) -> Self {
         QuerySelectField {
-            name: field.to_owned(),
+            name: field.into(),
             alias: None,
         }
     }
@@ -49,7 +81,7 @@ impl From<&str> for QuerySelectField {
 impl From<(&str, &str)> for QuerySelectField {
     fn from(fieldandalias: (&str, &str)) -> Self {
         QuerySelectField {
-            name: fieldandalias.0.to_owned(),
+            name: fieldandalias.0.into(),
             alias: Some(fieldandalias.1.to_owned()),
         }
     }
@@ -62,21 +94,21 @@ mod tests {
     fn test_select_field_1() {
         let field: QuerySelectField = QuerySelectField {
             alias: Some("uid".to_owned()),
-            name: "u.id".to_owned(),
+            name: "u.id".into(),
         };
 
-        assert_eq!(field.build(), "u.id AS uid");
+        assert_eq!(field.build(&mut ParamIter::default()).unwrap().query, "u.id AS uid");
     }
 
     #[test]
     fn test_select_field_2() {
         let field: QuerySelectField = "user_id".into();
-        assert_eq!(field.build(), "user_id");
+        assert_eq!(field.build(&mut ParamIter::default()).unwrap().query, "user_id");
     }
 
     #[test]
     fn test_select_field_3() {
         let field: QuerySelectField = ("user_id", "uid").into();
-        assert_eq!(field.build(), "user_id AS uid");
+        assert_eq!(field.build(&mut ParamIter::default()).unwrap().query, "user_id AS uid");
     }
 }
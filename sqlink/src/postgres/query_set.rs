@@ -1,32 +1,18 @@
-use crate::error::{Error};
-use crate::postgres::query_field::{QueryWithParamsLoc};
+use std::ops::Range;
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
 use crate::postgres::query_token::{QueryTokens};
-use crate::postgres::static_constant::FIELD_ESCAPE;
+use crate::postgres::ident::Ident;
 
-#[derive(Debug)]
-pub struct QuerySets(Vec<(String, QueryTokens)>);
+#[derive(Clone, Debug, Default)]
+pub struct QuerySets(Vec<(Ident, QueryTokens)>);
 impl QuerySets {
-    pub fn build_for_create(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
-        let mut v1: Vec<String> = Vec::new();
-        let mut v2: Vec<String> = Vec::new();
-        let mut p: Vec<usize> = Vec::new();
-        for (field, qtoken) in &self.0 {
-            v1.push(format!("{}{}{}", FIELD_ESCAPE, field, FIELD_ESCAPE));
-            let built = qtoken.build(i)?;
-            v2.push(built.query);
-            p.extend(built.parameters_loc);
-        }
-        Ok(QueryWithParamsLoc {
-            query: format!("({}) VALUES ({})", v1.join(","), v2.join(",")),
-            parameters_loc: p
-        })
-    }
-    pub fn build_for_update(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
+    pub fn build_for_update(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
         let mut v: Vec<String> = Vec::new();
         let mut p: Vec<usize> = Vec::new();
         for (field, qtoken) in &self.0 {
             let built = qtoken.build(i)?;
-            v.push(format!("{}{}{}={}", FIELD_ESCAPE, field, FIELD_ESCAPE, built.query));
+            v.push(format!("{}={}", field.to_sql(), built.query));
             p.extend(built.parameters_loc);
         }
         Ok(QueryWithParamsLoc {
@@ -34,14 +20,106 @@ impl QuerySets {
             parameters_loc: p
         })
     }
-    pub fn set(&mut self, field: (String, QueryTokens)) -> &mut Self {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn parameter_len(&self) -> usize {
+        self.0.iter().map(|(_, qtoken)| qtoken.parameter_len()).sum()
+    }
+    // the first field that is not the same as the other row, None when both rows have the same fields
+    fn first_different_field(&self, other: &QuerySets) -> Option<String> {
+        let len = std::cmp::max(self.0.len(), other.0.len());
+        (0..len).find_map(|idx| match (self.0.get(idx), other.0.get(idx)) {
+            (Some(a), Some(b)) if a.0 == b.0 => None,
+            (Some(a), _) => Some(a.0.display_name()),
+            (None, Some(b)) => Some(b.0.display_name()),
+            (None, None) => None,
+        })
+    }
+    pub fn set(&mut self, field: (Ident, QueryTokens)) -> &mut Self {
         self.0.push(field);
         self
     }
 }
 
-impl Default for QuerySets {
-    fn default() -> Self {
-        QuerySets(Vec::new())
+/// rows of a multi-row insert, every row share the fields of the first row
+#[derive(Clone, Debug, Default)]
+pub struct QueryRows(Vec<QuerySets>);
+impl QueryRows {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn set(&mut self, field: (Ident, QueryTokens)) -> &mut Self {
+        if self.0.is_empty() {
+            self.0.push(QuerySets::default());
+        }
+        let len = self.0.len();
+        self.0[len - 1].set(field);
+        self
+    }
+    pub fn new_row(&mut self) -> &mut Self {
+        if self.0.last().map(|row| row.len() > 0).unwrap_or(false) {
+            self.0.push(QuerySets::default());
+        }
+        self
+    }
+    /// split the rows so that each range does not have more than max parameters, including the fixed parameters of the statement
+    pub fn chunk_ranges(&self, fixed: usize, max: usize) -> Result<Vec<Range<usize>>, Error> {
+        // the same error as build_for_create, so no rows is never an empty list of chunks
+        if !self.0.first().map(|row| row.len() > 0).unwrap_or(false) {
+            return Err(Error::EmptySet { context: ErrorContext::default() });
+        }
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut start = 0;
+        let mut count = fixed;
+        for (idx, row) in self.0.iter().enumerate() {
+            let row_len = row.parameter_len();
+            // a single row can never fit
+            if fixed + row_len > max {
+                return Err(Error::ParameterOverflow {
+                    max,
+                    context: ErrorContext::default(),
+                });
+            }
+            if count + row_len > max {
+                ranges.push(start..idx);
+                start = idx;
+                count = fixed;
+            }
+            count += row_len;
+        }
+        if start < self.0.len() {
+            ranges.push(start..self.0.len());
+        }
+        Ok(ranges)
+    }
+    pub fn build_for_create(&self, range: Range<usize>, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let header = match self.0.first() {
+            Some(header) if header.len() > 0 => header,
+            _ => return Err(Error::EmptySet { context: ErrorContext::default() }),
+        };
+        let columns: Vec<String> = header.0.iter().map(|(field, _)| field.to_sql()).collect();
+        let mut values: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for idx in range {
+            let row = &self.0[idx];
+            if let Some(field) = row.first_different_field(header) {
+                return Err(Error::RowColumnMismatch {
+                    row: idx,
+                    context: ErrorContext::identifier(field),
+                });
+            }
+            let mut v: Vec<String> = Vec::new();
+            for (_, qtoken) in &row.0 {
+                let built = qtoken.build(i)?;
+                v.push(built.query);
+                p.extend(built.parameters_loc);
+            }
+            values.push(format!("({})", v.join(",")));
+        }
+        Ok(QueryWithParamsLoc {
+            query: format!("({}) VALUES {}", columns.join(","), values.join(",")),
+            parameters_loc: p
+        })
     }
 }
@@ -0,0 +1,230 @@
+use crate::error::Error;
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParamIter, ParameterValue};
+use crate::postgres::query_token::{TmpQueryTokens, TmpQueryToken, FormatQueryTup, mismatch_query};
+use crate::postgres::select_builder::SqlSelect;
+use crate::postgres::insert_builder::SqlInsert;
+use crate::postgres::update_builder::SqlUpdate;
+use crate::postgres::delete_builder::SqlDelete;
+use crate::postgres::compound_builder::SqlCompound;
+use crate::postgres::static_constant::PARAM_NOTATION;
+
+/// a statement embedded in another statement, either unbuilt or already built,
+/// insert, update and delete are only valid as the body of WITH
+#[derive(Clone)]
+pub enum SubQuery<'a> {
+    Select(SqlSelect<'a>),
+    Insert(SqlInsert<'a>),
+    Update(SqlUpdate<'a>),
+    Delete(SqlDelete<'a>),
+    Compound(SqlCompound<'a>),
+    Built(QueryWithParams<'a>),
+}
+
+impl<'a> From<SqlSelect<'a>> for SubQuery<'a> {
+    fn from(select: SqlSelect<'a>) -> Self {
+        SubQuery::Select(select)
+    }
+}
+
+impl<'a> From<SqlInsert<'a>> for SubQuery<'a> {
+    fn from(insert: SqlInsert<'a>) -> Self {
+        SubQuery::Insert(insert)
+    }
+}
+
+impl<'a> From<SqlUpdate<'a>> for SubQuery<'a> {
+    fn from(update: SqlUpdate<'a>) -> Self {
+        SubQuery::Update(update)
+    }
+}
+
+impl<'a> From<SqlDelete<'a>> for SubQuery<'a> {
+    fn from(delete: SqlDelete<'a>) -> Self {
+        SubQuery::Delete(delete)
+    }
+}
+
+impl<'a> From<SqlCompound<'a>> for SubQuery<'a> {
+    fn from(compound: SqlCompound<'a>) -> Self {
+        SubQuery::Compound(compound)
+    }
+}
+
+impl<'a> From<QueryWithParams<'a>> for SubQuery<'a> {
+    fn from(built: QueryWithParams<'a>) -> Self {
+        SubQuery::Built(built)
+    }
+}
+
+impl<'a> SubQuery<'a> {
+    /// the tokens of the subquery, without the parentheses.
+    /// into_parts of a builder moves its parameters out, they are shifted to after the parameters of the outer statement
+    pub(crate) fn into_format_query(self) -> FormatQueryTup<'a> {
+        let (statement, parameters) = match self {
+            SubQuery::Select(select) => {
+                let (select, parameters) = select.into_parts();
+                (QueryStatement::Select(Box::new(select)), parameters)
+            },
+            SubQuery::Insert(insert) => {
+                let (insert, parameters) = insert.into_parts();
+                (QueryStatement::Insert(Box::new(insert)), parameters)
+            },
+            SubQuery::Update(update) => {
+                let (update, parameters) = update.into_parts();
+                (QueryStatement::Update(Box::new(update)), parameters)
+            },
+            SubQuery::Delete(delete) => {
+                let (delete, parameters) = delete.into_parts();
+                (QueryStatement::Delete(Box::new(delete)), parameters)
+            },
+            SubQuery::Compound(compound) => {
+                let (compound, parameters) = compound.into_parts();
+                (QueryStatement::Compound(Box::new(compound)), parameters)
+            },
+            SubQuery::Built(built) => return tokenize_built_query(built),
+        };
+        let qsubquery = QuerySubQuery {
+            statement,
+            offset: 0,
+            parameter_len: parameters.len(),
+        };
+        (TmpQueryTokens(vec![TmpQueryToken::SubQuery(qsubquery)]), parameters)
+    }
+}
+
+// split a built query on its placeholders, each occurrence of $n becomes a parameter of its own with a copy of the nth value,
+// so a placeholder used twice is still renumbered in both places. The placeholders inside quotes are left as they are.
+// A placeholder without a value, or a value without a placeholder, is ArgumentCountMismatch
+fn tokenize_built_query(built: QueryWithParams) -> FormatQueryTup {
+    let QueryWithParams { query, parameters } = built;
+    let mut tokens: Vec<TmpQueryToken> = Vec::new();
+    let mut params: Vec<ParameterValue> = Vec::new();
+    let mut used = vec![false; parameters.len()];
+    let mut highest = 0;
+    let mut quote: Option<char> = None;
+    let mut prev = 0;
+    let mut chars = query.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => {},
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, c) if PARAM_NOTATION.starts_with(c) => {
+                let mut end = pos + c.len_utf8();
+                while let Some((_, digit)) = chars.next_if(|(_, d)| d.is_ascii_digit()) {
+                    end += digit.len_utf8();
+                }
+                let n: usize = match query[pos + c.len_utf8()..end].parse() {
+                    Ok(n) => n,
+                    Err(_) => continue,
+                };
+                highest = highest.max(n);
+                if n == 0 || n > parameters.len() {
+                    continue;
+                }
+                used[n - 1] = true;
+                if prev < pos {
+                    tokens.push(TmpQueryToken::Sql(query[prev..pos].to_owned()));
+                }
+                tokens.push(TmpQueryToken::Parameter);
+                params.push(parameters[n - 1].clone());
+                prev = end;
+            },
+            _ => {},
+        }
+    }
+    let referenced = used.iter().filter(|u| **u).count();
+    if highest > parameters.len() || referenced != parameters.len() {
+        let expected = if highest > parameters.len() { highest } else { referenced };
+        return mismatch_query(query, expected, parameters.len());
+    }
+    if prev < query.len() {
+        tokens.push(TmpQueryToken::Sql(query[prev..].to_owned()));
+    }
+    (TmpQueryTokens(tokens), params)
+}
+
+#[derive(Clone, Debug)]
+pub enum QueryStatement {
+    Select(Box<SqlSelect<'static>>),
+    Insert(Box<SqlInsert<'static>>),
+    Update(Box<SqlUpdate<'static>>),
+    Delete(Box<SqlDelete<'static>>),
+    Compound(Box<SqlCompound<'static>>),
+}
+
+/// an unbuilt statement whose parameters are moved into the outer statement, starting at offset
+#[derive(Clone, Debug)]
+pub struct QuerySubQuery {
+    statement: QueryStatement,
+    offset: usize,
+    parameter_len: usize,
+}
+
+impl QuerySubQuery {
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let built = match &self.statement {
+            QueryStatement::Select(select) => select.build_loc(i)?,
+            QueryStatement::Insert(insert) => insert.build_loc(i)?,
+            QueryStatement::Update(update) => update.build_loc(i)?,
+            QueryStatement::Delete(delete) => delete.build_loc(i)?,
+            QueryStatement::Compound(compound) => compound.build_loc(i)?,
+        };
+        Ok(QueryWithParamsLoc {
+            query: built.query,
+            parameters_loc: built.parameters_loc.into_iter().map(|p| p + self.offset).collect(),
+        })
+    }
+    pub fn parameter_len(&self) -> usize {
+        self.parameter_len
+    }
+    pub fn shift(mut self, offset: usize) -> Self {
+        self.offset += offset;
+        self
+    }
+}
+
+/// wrap a select into parentheses, to use with where, compose_query, etc
+pub fn subquery<'a, S: Into<SubQuery<'a>>>(sub: S) -> FormatQueryTup<'a> {
+    let (tokens, parameters) = sub.into().into_format_query();
+    let mut v: Vec<TmpQueryToken> = Vec::with_capacity(tokens.0.len() + 2);
+    v.push(TmpQueryToken::Sql("(".to_owned()));
+    v.extend(tokens.0);
+    v.push(TmpQueryToken::Sql(")".to_owned()));
+    (TmpQueryTokens(v), parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn built(query: &str, parameters: Vec<ParameterValue<'static>>) -> QueryWithParams<'static> {
+        QueryWithParams { query: query.to_owned(), parameters }
+    }
+    #[test]
+    fn test_tokenize_built_query() {
+        let parameters = (1..=10).map(ParameterValue::owned).collect();
+        let (tokens, parameters) = tokenize_built_query(built("a = $1 AND b IN ($2,$3,$4,$5,$6,$7,$8,$9,$10) AND c = '$1' AND \"$1\" = 1", parameters));
+        let qtokens = tokens.into_query_tokens(0);
+        assert_eq!(qtokens.parameter_len(), 10);
+        assert_eq!(parameters.len(), 10);
+        assert_eq!(qtokens.build(&mut ParamIter::from(11)).unwrap().query, "a = $11 AND b IN ($12,$13,$14,$15,$16,$17,$18,$19,$20) AND c = '$1' AND \"$1\" = 1");
+    }
+    #[test]
+    fn test_tokenize_built_query_repeated() {
+        let (tokens, parameters) = tokenize_built_query(built("a = $2 OR b = $1 OR c = $2", vec![ParameterValue::owned(5), ParameterValue::owned(6)]));
+        let qtokens = tokens.into_query_tokens(1);
+        assert_eq!(qtokens.build(&mut ParamIter::from(2)).unwrap().query, "a = $2 OR b = $3 OR c = $4");
+        assert_eq!(format!("{:?}", parameters), "[6, 5, 6]");
+    }
+    #[test]
+    fn test_tokenize_built_query_mismatch() {
+        for (query, len, expected) in &[("a = $1 AND b = $3", 2, 3), ("a = $1", 2, 1), ("a = $0", 1, 0)] {
+            let parameters = (1..=*len as i32).map(ParameterValue::owned).collect();
+            let (tokens, _) = tokenize_built_query(built(query, parameters));
+            match tokens.into_query_tokens(0).build(&mut ParamIter::default()) {
+                Err(Error::ArgumentCountMismatch { expected: e, given, .. }) => assert_eq!((e, given), (*expected, *len), "{}", query),
+                _ => panic!("{}", query),
+            }
+        }
+    }
+}
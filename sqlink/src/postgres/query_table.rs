@@ -1,28 +1,40 @@
-use crate::error::Error;
-use crate::postgres::query_field::{QueryWithParamsLoc};
+use crate::error::{Error, ErrorContext, Clause};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
 use crate::postgres::query_where::{QueryWheres, WhereOperator};
-use crate::postgres::static_constant::TABLE_ESCAPE;
+use crate::postgres::query_token::{QueryTokens};
+use crate::postgres::ident::{Ident, Alias};
 
-#[derive(Debug)]
-pub struct QueryTables(Vec<QueryTable>);
-
-impl Default for QueryTables {
-    fn default() -> Self {
-        QueryTables(Vec::new())
-    }
+#[derive(Clone, Debug, Default)]
+pub struct QueryTables {
+    tables: Vec<QueryTable>,
+    error: Option<Error>, // join / on misuse, returned on build
 }
 
 impl QueryTables {
-    pub fn len(&self) -> usize {
-        self.0.len()
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.tables.is_empty() {
+            return Err(Error::MissingTable { context: ErrorContext::default() });
+        }
+        if self.tables.len() != 1 {
+            let names: Vec<String> = self.tables.iter().map(|t| t.display_name()).collect();
+            return Err(Error::TableCount {
+                count: self.tables.len(),
+                context: ErrorContext::identifier(names.join(",")),
+            });
+        }
+        self.build_list(i)
     }
-    pub fn build(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
-        if self.0.len() != 1 {
-            return Err(Error::Syntax("Table can only select 1 time".into()));
+    // the tables separated by , without checking the count
+    fn build_list(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
         }
         let mut v: Vec<String> = Vec::new();
         let mut q: Vec<usize> = Vec::new();
-        for join in &self.0 {
+        for join in &self.tables {
             let built = join.build(i)?;
             v.push(built.query);
             q.extend(built.parameters_loc);
@@ -33,46 +45,62 @@ impl QueryTables {
         })
     }
     pub fn push(&mut self, field: QueryTable) {
-        self.0.push(field);
+        self.tables.push(field);
     }
-    pub fn get_last_table(&mut self) -> &mut QueryTable {
-        let len = self.0.len();
-        if len == 0 {
-            panic!("cannot select without base table");
+    // keep only the first error, the later ones are usually caused by it
+    fn set_error(&mut self, error: Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
         }
-        &mut self.0[len - 1]
     }
-    pub fn inner_join(&mut self, query_table: QueryTable) -> &mut Self {
-        let table = self.get_last_table();
-        table.table_join.push(TableJoin::InnerJoin(query_table, QueryWheres::default()));
+    pub fn get_last_table(&mut self) -> Option<&mut QueryTable> {
+        self.tables.last_mut()
+    }
+    fn push_join(&mut self, join: TableJoin) -> &mut Self {
+        match self.get_last_table() {
+            Some(table) => table.table_join.push(join),
+            None => {
+                let name = join.table().display_name();
+                self.set_error(Error::MissingTable {
+                    context: ErrorContext {
+                        builder: None,
+                        clause: Some(Clause::Join),
+                        identifier: Some(name),
+                    },
+                });
+            },
+        }
         self
     }
+    pub fn inner_join(&mut self, query_table: QueryTable) -> &mut Self {
+        self.push_join(TableJoin::InnerJoin(query_table, QueryWheres::default()))
+    }
     pub fn left_join(&mut self, query_table: QueryTable) -> &mut Self {
-        let table = self.get_last_table();
-        table.table_join.push(TableJoin::LeftJoin(query_table, QueryWheres::default()));
-        self
+        self.push_join(TableJoin::LeftJoin(query_table, QueryWheres::default()))
     }
     pub fn right_join(&mut self, query_table: QueryTable) -> &mut Self {
-        let table = self.get_last_table();
-        table.table_join.push(TableJoin::RightJoin(query_table, QueryWheres::default()));
-        self
+        self.push_join(TableJoin::RightJoin(query_table, QueryWheres::default()))
     }
     pub fn full_join(&mut self, query_table: QueryTable) -> &mut Self {
-        let table = self.get_last_table();
-        table.table_join.push(TableJoin::FullJoin(query_table, QueryWheres::default()));
-        self
+        self.push_join(TableJoin::FullJoin(query_table, QueryWheres::default()))
     }
     pub fn on(&mut self, query_where: QueryWheres) -> &mut Self {
-        let table = self.get_last_table();
-        let len = table.table_join.len();
-        if len == 0 {
-            panic!("cannot on without join table");
-        }
-        let w = match &mut table.table_join[len - 1] {
-            TableJoin::InnerJoin(_, w) => w,
-            TableJoin::LeftJoin(_, w) => w,
-            TableJoin::RightJoin(_, w) => w,
-            TableJoin::FullJoin(_, w) => w,
+        let join = match self.get_last_table() {
+            Some(table) => table.table_join.last_mut(),
+            None => {
+                self.set_error(Error::MissingTable { context: ErrorContext::clause(Clause::Join) });
+                return self;
+            },
+        };
+        let w = match join {
+            Some(TableJoin::InnerJoin(_, w)) => w,
+            Some(TableJoin::LeftJoin(_, w)) => w,
+            Some(TableJoin::RightJoin(_, w)) => w,
+            Some(TableJoin::FullJoin(_, w)) => w,
+            None => {
+                self.set_error(Error::OnWithoutJoin { context: ErrorContext::clause(Clause::Join) });
+                return self;
+            },
         };
         if w.len() != 0 {
             w.push(WhereOperator::And);
@@ -82,7 +110,88 @@ impl QueryTables {
     }
 }
 
-#[derive(Debug)]
+/// the other tables of UPDATE ... FROM and DELETE ... USING
+///
+/// postgres cannot join the updated / deleted table, so each inner join before from / using becomes one of these tables
+/// with the ON condition moved into WHERE, and an outer join on it is an error.
+/// once from / using is called, the later joins are joined to its last table as in a select
+#[derive(Clone, Debug, Default)]
+pub struct QueryFroms {
+    tables: QueryTables,
+    join_wheres: QueryWheres,
+    joined_to_last: bool,
+}
+
+impl QueryFroms {
+    pub fn is_empty(&self) -> bool {
+        self.tables.tables.is_empty() && self.tables.error.is_none()
+    }
+    pub fn push(&mut self, table: QueryTable) {
+        self.tables.push(table);
+        self.joined_to_last = true;
+    }
+    pub fn join(&mut self, join: TableJoin) {
+        if self.joined_to_last {
+            self.tables.push_join(join);
+            return;
+        }
+        match join {
+            TableJoin::InnerJoin(table, on) => {
+                if on.len() == 0 {
+                    self.tables.set_error(Error::JoinWithoutOn {
+                        context: ErrorContext {
+                            builder: None,
+                            clause: Some(Clause::Join),
+                            identifier: Some(table.display_name()),
+                        },
+                    });
+                    return;
+                }
+                self.tables.push(table);
+                if self.join_wheres.len() > 0 {
+                    self.join_wheres.push(WhereOperator::And);
+                }
+                // the ON can contain OR, so it is wrapped before it is joined with the other conditions by AND
+                self.join_wheres.push(WhereOperator::Open);
+                self.join_wheres.extend(on);
+                self.join_wheres.push(WhereOperator::Close);
+            },
+            join => {
+                self.tables.set_error(Error::Syntax {
+                    message: "the target table cannot be outer joined, join a table given to from / using instead".to_owned(),
+                    context: ErrorContext {
+                        builder: None,
+                        clause: Some(Clause::Join),
+                        identifier: Some(join.table().display_name()),
+                    },
+                });
+            },
+        }
+    }
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        self.tables.build_list(i)
+    }
+    /// the ON conditions of the inner joins, then the where of the statement
+    pub fn build_wheres(&self, wheres: &QueryWheres, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let built_for_join = self.join_wheres.build(i)?;
+        let built_for_where = wheres.build(i)?;
+        if built_for_join.query.is_empty() {
+            return Ok(built_for_where);
+        }
+        if built_for_where.query.is_empty() {
+            return Ok(built_for_join);
+        }
+        let mut p = built_for_join.parameters_loc;
+        p.extend(built_for_where.parameters_loc);
+        Ok(QueryWithParamsLoc {
+            query: format!("{} AND ({})", built_for_join.query, built_for_where.query),
+            parameters_loc: p,
+        })
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug)]
 pub enum TableJoin {
     InnerJoin(QueryTable, QueryWheres),
     LeftJoin(QueryTable, QueryWheres),
@@ -90,31 +199,69 @@ pub enum TableJoin {
     FullJoin(QueryTable, QueryWheres),
 }
 
-#[derive(Debug)]
+impl TableJoin {
+    fn table(&self) -> &QueryTable {
+        match self {
+            TableJoin::InnerJoin(t, _) => t,
+            TableJoin::LeftJoin(t, _) => t,
+            TableJoin::RightJoin(t, _) => t,
+            TableJoin::FullJoin(t, _) => t,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct QueryTable {
     alias: Option<String>,
-    name: String,
-    schema: Option<String>,
+    name: Ident, // the schema is the part before the table name
+    subquery: Option<QueryTokens>, // derived table, name is not used
     table_join: Vec<TableJoin>,
 }
 
 impl QueryTable {
+    /// (SELECT ...) AS alias
+    pub fn subquery(qtokens: QueryTokens, alias: String) -> Self {
+        QueryTable {
+            alias: Some(alias),
+            name: Ident::from_parts(Vec::<String>::new()),
+            subquery: Some(qtokens),
+            table_join: Vec::new(),
+        }
+    }
+    fn build_table_name(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        if let Some(qtokens) = &self.subquery {
+            let built = qtokens.build(i)?;
+            return Ok(QueryWithParamsLoc {
+                query: format!("({}) AS {}", built.query, self.alias.clone().unwrap_or_default()),
+                parameters_loc: built.parameters_loc,
+            });
+        }
+        Ok(QueryWithParamsLoc {
+            query: self.get_table_name(),
+            parameters_loc: Vec::new(),
+        })
+    }
+    // the name used in error messages, derived tables only have the alias
+    fn display_name(&self) -> String {
+        if self.subquery.is_some() {
+            return self.alias.clone().unwrap_or_default();
+        }
+        self.name.display_name()
+    }
     fn get_table_name(&self) -> String {
-        let escaped_table_name = if let Some(schema) = &self.schema {
-            format!("{}{}{}.{}{}{}", TABLE_ESCAPE, schema, TABLE_ESCAPE, TABLE_ESCAPE, self.name, TABLE_ESCAPE)
-        } else {
-            format!("{}{}{}", TABLE_ESCAPE, self.name.clone(), TABLE_ESCAPE)
-        };
+        let escaped_table_name = self.name.to_sql();
         if let Some(alias) = &self.alias {
             format!("{} AS {}", escaped_table_name, alias)
         } else {
             escaped_table_name
         }
     }
-    fn build(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
+    fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
         let mut v: Vec<String> = Vec::new();
         let mut p: Vec<usize> = Vec::new();
-        v.push(self.get_table_name());
+        let built_for_name = self.build_table_name(i)?;
+        v.push(built_for_name.query);
+        p.extend(built_for_name.parameters_loc);
         for join in &self.table_join {
             let (op, join_table, qw) = match join {
                 TableJoin::InnerJoin(join_table, qw) => {("INNER JOIN", join_table, qw)},
@@ -123,9 +270,20 @@ impl QueryTable {
                 TableJoin::FullJoin(join_table, qw) => {("FULL JOIN", join_table, qw)},
             };
             v.push(op.to_owned());
-            v.push(join_table.get_table_name());
-            v.push("ON".to_owned());
+            let built_for_join_name = join_table.build_table_name(i)?;
+            v.push(built_for_join_name.query);
+            p.extend(built_for_join_name.parameters_loc);
             let qwresult = qw.build(i)?;
+            if qwresult.query.is_empty() {
+                return Err(Error::JoinWithoutOn {
+                    context: ErrorContext {
+                        builder: None,
+                        clause: Some(Clause::Join),
+                        identifier: Some(join_table.display_name()),
+                    },
+                });
+            }
+            v.push("ON".to_owned());
             v.push(qwresult.query);
             p.extend(qwresult.parameters_loc);
         }
@@ -135,36 +293,43 @@ impl QueryTable {
         })
     }
 }
-impl From<&str> for QueryTable {
-    fn from(table: &str) -> Self {
+impl From<Ident> for QueryTable {
+    fn from(name: Ident) -> Self {
         QueryTable {
             alias: None,
-            name: table.to_owned(),
-            schema: None,
+            name,
+            subquery: None,
             table_join: Vec::new(),
         }
     }
 }
 
-impl From<(&str, &str)> for QueryTable {
-    fn from(nameandalias: (&str, &str)) -> Self {
+/// the alias is written as is, give an Ident to quote it
+impl<S: Into<Alias>> From<(Ident, S)> for QueryTable {
+    fn from(nameandalias: (Ident, S)) -> Self {
         QueryTable {
-            alias: Some(nameandalias.1.to_owned()),
-            name: nameandalias.0.to_owned(),
-            schema: None,
-            table_join: Vec::new(),
+            alias: Some(nameandalias.1.into().to_sql()),
+            ..nameandalias.0.into()
         }
     }
 }
 
-impl From<(&str, &str, &str)> for QueryTable {
-    fn from(schemaxnamexalias: (&str, &str, &str)) -> Self {
-        QueryTable {
-            alias: Some(schemaxnamexalias.2.to_owned()),
-            name: schemaxnamexalias.1.to_owned(),
-            schema: Some(schemaxnamexalias.0.to_owned()),
-            table_join: Vec::new(),
-        }
+// split on "." the same as Ident, "public.user" is the table user of the schema public
+impl From<&str> for QueryTable {
+    fn from(table: &str) -> Self {
+        Ident::new(table).into()
+    }
+}
+
+impl<S: Into<Alias>> From<(&str, S)> for QueryTable {
+    fn from(nameandalias: (&str, S)) -> Self {
+        (Ident::new(nameandalias.0), nameandalias.1).into()
+    }
+}
+
+impl<S: Into<Alias>> From<(&str, &str, S)> for QueryTable {
+    fn from(schemaxnamexalias: (&str, &str, S)) -> Self {
+        (Ident::from_parts(vec![schemaxnamexalias.0, schemaxnamexalias.1]), schemaxnamexalias.2).into()
     }
 }
 
@@ -175,23 +340,73 @@ mod tests {
     fn test_table_1() {
         let table: QueryTable = QueryTable {
             alias: Some("u".to_owned()),
-            name: "user".to_owned(),
-            schema: Some("public".to_owned()),
+            name: Ident::new("public.user"),
+            subquery: None,
             table_join: Vec::new(),
         };
 
-        assert_eq!(table.build(&mut 0).unwrap().query, "\"public\".\"user\" AS u");
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"public\".\"user\" AS u");
     }
 
     #[test]
     fn test_table_2() {
         let table: QueryTable = "user".into();
-        assert_eq!(table.build(&mut 0).unwrap().query, "\"user\"");
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"user\"");
     }
 
     #[test]
     fn test_table_3() {
         let table: QueryTable = ("user", "u").into();
-        assert_eq!(table.build(&mut 0).unwrap().query, "\"user\" AS u");
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"user\" AS u");
+    }
+
+    #[test]
+    fn test_table_schema() {
+        let table: QueryTable = "public.user".into();
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"public\".\"user\"");
+        let table: QueryTable = ("public.user", "u").into();
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"public\".\"user\" AS u");
+    }
+
+    #[test]
+    fn test_table_ident() {
+        let table: QueryTable = "my\"table".into();
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"my\"\"table\"");
+        let table: QueryTable = (Ident::new("public.user"), Ident::new("U")).into();
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"public\".\"user\" AS \"U\"");
+        assert_eq!(table.display_name(), "public.user");
+        let table: QueryTable = ("user", Ident::new("U")).into();
+        assert_eq!(table.build(&mut ParamIter::default()).unwrap().query, "\"user\" AS \"U\"");
+    }
+
+    #[test]
+    fn test_tables_deferred_error() {
+        let mut tables = QueryTables::default();
+        tables.inner_join("user".into());
+        tables.push("person".into());
+        let error = tables.build(&mut ParamIter::default()).unwrap_err();
+        assert!(matches!(error, Error::MissingTable { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("user"));
+
+        let mut tables = QueryTables::default();
+        tables.push("person".into());
+        tables.on(QueryWheres::from(QueryTokens::from("1 = 1")));
+        assert!(matches!(tables.build(&mut ParamIter::default()), Err(Error::OnWithoutJoin { .. })));
+
+        let mut tables = QueryTables::default();
+        tables.push("person".into());
+        tables.left_join("user".into());
+        let error = tables.build(&mut ParamIter::default()).unwrap_err();
+        assert!(matches!(error, Error::JoinWithoutOn { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("user"));
+
+        assert!(matches!(QueryTables::default().build(&mut ParamIter::default()), Err(Error::MissingTable { .. })));
+
+        let mut tables = QueryTables::default();
+        tables.push("person".into());
+        tables.push(("public", "user", "u").into());
+        let error = tables.build(&mut ParamIter::default()).unwrap_err();
+        assert!(matches!(error, Error::TableCount { count: 2, .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("person,public.user"));
     }
 }
@@ -1,33 +1,54 @@
-use crate::postgres::query_field::{ParameterValueAsRef, QueryWithParamsLoc};
-use crate::postgres::static_constant::PARAM_NOTATION;
-use crate::error::{Error};
+use crate::postgres::query_field::{ParameterValue, ParameterValueAsRef, QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_subquery::QuerySubQuery;
+use crate::error::{Error, ErrorContext};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct QueryTokens(pub Vec<QueryToken>);
 impl QueryTokens {
-    pub fn build(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
         let mut query = String::from("");
         let mut parameters_loc = Vec::new();
         {
             for wo in &self.0 {
                 match wo {
-                    QueryToken::Sql(s) => { query.push_str(&s); },
+                    QueryToken::Sql(s) => { query.push_str(s); },
                     QueryToken::ParameterLoc(p) => {
-                        query.push_str(&format!("{}{}", PARAM_NOTATION, i));
-                        parameters_loc.push(p.clone());
-                        *i += 1;
+                        query.push_str(&i.next_placeholder()?);
+                        parameters_loc.push(*p);
                     },
+                    QueryToken::SubQuery(sub) => {
+                        let built = sub.build(i)?;
+                        query.push_str(&built.query);
+                        parameters_loc.extend(built.parameters_loc);
+                    },
+                    QueryToken::Error(e) => return Err(e.clone()),
                 }
             }
         }
         Ok(QueryWithParamsLoc {
-            query: query,
-            parameters_loc: parameters_loc,
+            query,
+            parameters_loc,
         })
     }
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    pub fn parameter_len(&self) -> usize {
+        self.0.iter().map(|t| match t {
+            QueryToken::Sql(_) | QueryToken::Error(_) => 0,
+            QueryToken::ParameterLoc(_) => 1,
+            QueryToken::SubQuery(sub) => sub.parameter_len(),
+        }).sum()
+    }
+    /// the sql with {} for parameters, to compare 2 expressions without building them
+    pub fn shape(&self) -> String {
+        self.0.iter().map(|t| match t {
+            QueryToken::Sql(s) => s.as_str(),
+            QueryToken::ParameterLoc(_) => "{}",
+            QueryToken::SubQuery(_) => "({})",
+            QueryToken::Error(_) => "",
+        }).collect::<String>().trim().to_owned()
+    }
     pub fn push(&mut self, field: QueryToken) {
         self.0.push(field);
     }
@@ -35,68 +56,131 @@ impl QueryTokens {
         self.0.extend(qwhere.0);
     }
 }
-impl Default for QueryTokens {
-    fn default() -> Self {
-        QueryTokens(Vec::new())
+impl From<&str> for QueryTokens {
+    fn from(sql: &str) -> Self {
+        QueryTokens(vec![QueryToken::Sql(sql.to_owned())])
     }
 }
-#[derive(Debug)]
+impl From<String> for QueryTokens {
+    fn from(sql: String) -> Self {
+        QueryTokens(vec![QueryToken::Sql(sql)])
+    }
+}
+#[derive(Clone, Debug)]
 pub enum QueryToken {
     Sql(String),
     ParameterLoc(usize),
+    SubQuery(QuerySubQuery),
+    /// deferred error from building the tokens, returned on build
+    Error(Error),
 }
+#[derive(Clone, Debug)]
 pub struct TmpQueryTokens(pub Vec<TmpQueryToken>);
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum TmpQueryToken {
     Sql(String),
     Parameter,
+    SubQuery(QuerySubQuery),
+    Error(Error),
 }
 impl TmpQueryTokens {
     // from will be the current paramvec.len()
-    pub fn to_query_tokens(&self, from: usize) -> QueryTokens {
+    pub fn into_query_tokens(self, from: usize) -> QueryTokens {
         let mut qtokens = QueryTokens::default();
         let mut iter = from;
-        for i in &self.0 {
+        for i in self.0 {
             match i {
-                TmpQueryToken::Sql(s) => qtokens.push(QueryToken::Sql(s.clone())),
+                TmpQueryToken::Sql(s) => qtokens.push(QueryToken::Sql(s)),
                 TmpQueryToken::Parameter => {
                     qtokens.push(QueryToken::ParameterLoc(iter));
                     iter += 1;
                 },
+                TmpQueryToken::SubQuery(sub) => {
+                    let len = sub.parameter_len();
+                    qtokens.push(QueryToken::SubQuery(sub.shift(iter)));
+                    iter += len;
+                },
+                TmpQueryToken::Error(e) => qtokens.push(QueryToken::Error(e)),
             }
         }
         qtokens
     }
 }
-pub type FormatQueryTup<'a> = (TmpQueryTokens, Vec<ParameterValueAsRef<'a>>);
-pub fn format_query<S: Into<String>>(query: S, arg: Vec<ParameterValueAsRef>) -> (TmpQueryTokens, Vec<ParameterValueAsRef>) {
-    let mut argiter = 0;
-    let mut qtoken: Vec<TmpQueryToken> = Vec::new();
-    let queryvec: Vec<char> = query.into().chars().collect();
+pub type FormatQueryTup<'a> = (TmpQueryTokens, Vec<ParameterValue<'a>>);
+// split the query on {}, None is the placeholder
+fn split_placeholder(query: &str) -> Vec<Option<String>> {
+    let mut parts: Vec<Option<String>> = Vec::new();
+    let queryvec: Vec<char> = query.chars().collect();
     let mut cur: usize = 0;
     let mut prevcur: usize = 0;
     while cur < queryvec.len() {
-        if queryvec[cur] == '{' && cur + 1 < queryvec.len() {
-            if queryvec[cur + 1] == '}' {
-                if prevcur != cur {
-                    let querypart: String = queryvec[prevcur..cur].iter().cloned().collect();
-                    qtoken.push(TmpQueryToken::Sql(querypart));
-                }
-                qtoken.push(TmpQueryToken::Parameter); // in future will be checked with macro already
-                argiter += 1;
-                cur += 2;
-                prevcur = cur;
+        if queryvec[cur] == '{' && cur + 1 < queryvec.len() && queryvec[cur + 1] == '}' {
+            if prevcur != cur {
+                parts.push(Some(queryvec[prevcur..cur].iter().cloned().collect()));
             }
+            parts.push(None);
+            cur += 2;
+            prevcur = cur;
         } else {
             cur += 1;
         }
     }
     if prevcur != cur {
-        let querypart: String = queryvec[prevcur..cur].iter().cloned().collect();
-        qtoken.push(TmpQueryToken::Sql(querypart));
+        parts.push(Some(queryvec[prevcur..cur].iter().cloned().collect()));
+    }
+    parts
+}
+// the fluent api cannot return Result, so the error is kept as a token until build
+pub(crate) fn mismatch_query<'a>(query: String, expected: usize, given: usize) -> FormatQueryTup<'a> {
+    (TmpQueryTokens(vec![TmpQueryToken::Error(Error::ArgumentCountMismatch {
+        expected,
+        given,
+        context: ErrorContext::identifier(query),
+    })]), Vec::new())
+}
+pub fn format_query<S: Into<String>>(query: S, arg: Vec<ParameterValueAsRef>) -> (TmpQueryTokens, Vec<ParameterValue>) {
+    format_query_values(query, arg.into_iter().map(ParameterValue::Borrowed).collect())
+}
+/// same as format_query, but the arguments can be owned with ParameterValue::owned
+pub fn format_query_values<S: Into<String>>(query: S, arg: Vec<ParameterValue>) -> (TmpQueryTokens, Vec<ParameterValue>) {
+    let mut argiter = 0;
+    let mut qtoken: Vec<TmpQueryToken> = Vec::new();
+    let query: String = query.into();
+    for part in split_placeholder(&query) {
+        match part {
+            Some(querypart) => qtoken.push(TmpQueryToken::Sql(querypart)),
+            None => {
+                qtoken.push(TmpQueryToken::Parameter); // in future will be checked with macro already
+                argiter += 1;
+            },
+        }
     }
     if arg.len() != argiter {
-        panic!("argument len does not match with query");
+        return mismatch_query(query, argiter, arg.len());
     }
     (TmpQueryTokens(qtoken), arg)
 }
+/// same as format_query, but each {} is replaced by another FormatQueryTup, such as subquery() or format_query()
+pub fn compose_query<'a, S: Into<String>>(query: S, parts: Vec<FormatQueryTup<'a>>) -> FormatQueryTup<'a> {
+    let query: String = query.into();
+    let split = split_placeholder(&query);
+    let expected = split.iter().filter(|part| part.is_none()).count();
+    if parts.len() != expected {
+        return mismatch_query(query, expected, parts.len());
+    }
+    let mut qtoken: Vec<TmpQueryToken> = Vec::new();
+    let mut params: Vec<ParameterValue<'a>> = Vec::new();
+    let mut parts_iter = parts.into_iter();
+    for part in split {
+        match part {
+            Some(querypart) => qtoken.push(TmpQueryToken::Sql(querypart)),
+            None => {
+                if let Some((tokens, p)) = parts_iter.next() {
+                    qtoken.extend(tokens.0);
+                    params.extend(p);
+                }
+            },
+        }
+    }
+    (TmpQueryTokens(qtoken), params)
+}
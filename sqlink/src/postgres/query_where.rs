@@ -1,27 +1,23 @@
-use crate::error::Error;
-use crate::postgres::query_field::{QueryWithParamsLoc};
+use crate::error::{Error, ErrorContext};
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
 use crate::postgres::query_token::{QueryTokens, QueryToken};
-use crate::postgres::static_constant::PARAM_NOTATION;
+use crate::postgres::query_subquery::QuerySubQuery;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum WhereOperator {
     Sql(String),
     ParameterLoc(usize),
+    SubQuery(QuerySubQuery),
     And,
     Or,
     Open,
     Close,
+    Error(Error),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct QueryWheres(Vec<WhereOperator>);
 
-impl Default for QueryWheres {
-    fn default() -> Self {
-        QueryWheres(Vec::new())
-    }
-}
-
 impl From<QueryTokens> for QueryWheres {
     fn from(qtokens: QueryTokens) -> Self {
         let mut qw = Vec::with_capacity(qtokens.0.len());
@@ -29,6 +25,8 @@ impl From<QueryTokens> for QueryWheres {
             let op = match token {
                 QueryToken::Sql(s) => WhereOperator::Sql(s),
                 QueryToken::ParameterLoc(p) => WhereOperator::ParameterLoc(p),
+                QueryToken::SubQuery(sub) => WhereOperator::SubQuery(sub),
+                QueryToken::Error(e) => WhereOperator::Error(e),
             };
             qw.push(op);
         }
@@ -36,34 +34,91 @@ impl From<QueryTokens> for QueryWheres {
     }
 }
 
+// a ( ... ) group while building, the outermost one is the whole where
+#[derive(Default)]
+struct WhereGroup {
+    connector: Option<&'static str>, // connector in front of the group
+    pending: Option<&'static str>, // connector in front of the next condition
+    query: String,
+    parameters_loc: Vec<usize>,
+}
+
+impl WhereGroup {
+    // a connector is only written between conditions, so ( AND x) becomes (x)
+    fn push_connector(&mut self, connector: Option<&'static str>) {
+        if let Some(connector) = connector {
+            if !self.query.is_empty() {
+                self.query.push_str(connector);
+            }
+        }
+    }
+    fn push_condition(&mut self, sql: &str) {
+        let pending = self.pending.take();
+        self.push_connector(pending);
+        self.query.push_str(sql);
+    }
+}
+
 impl QueryWheres {
-    pub fn build(&self, i: &mut i8) -> Result<QueryWithParamsLoc, Error> {
-        let mut query = String::from("");
-        let mut parameters = Vec::new();
-        {
-            for wo in &self.0 {
-                match wo {
-                    WhereOperator::Sql(s) => { query.push_str(&s); },
-                    WhereOperator::ParameterLoc(p) => {
-                        query.push_str(&format!("{}{}", PARAM_NOTATION, i));
-                        parameters.push(p.clone());
-                        *i += 1;
-                    },
-                    WhereOperator::And => { query.push_str(" AND "); },
-                    WhereOperator::Or => { query.push_str(" OR "); },
-                    WhereOperator::Open => { query.push_str("("); },
-                    WhereOperator::Close => { query.push_str(")"); },
-                }
+    /// empty groups are dropped together with their connector, so the where can be empty after build even when a group was opened,
+    /// unbalanced groups is an error
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut parents: Vec<WhereGroup> = Vec::new();
+        let mut group = WhereGroup::default();
+        for wo in &self.0 {
+            match wo {
+                WhereOperator::Sql(s) => group.push_condition(s),
+                WhereOperator::ParameterLoc(p) => {
+                    group.push_condition(&i.next_placeholder()?);
+                    group.parameters_loc.push(*p);
+                },
+                WhereOperator::SubQuery(sub) => {
+                    let built = sub.build(i)?;
+                    group.push_condition(&built.query);
+                    group.parameters_loc.extend(built.parameters_loc);
+                },
+                WhereOperator::And => { group.pending = Some(" AND "); },
+                WhereOperator::Or => { group.pending = Some(" OR "); },
+                WhereOperator::Open => {
+                    let connector = group.pending.take();
+                    parents.push(std::mem::replace(&mut group, WhereGroup {
+                        connector,
+                        ..WhereGroup::default()
+                    }));
+                },
+                WhereOperator::Close => {
+                    let parent = match parents.pop() {
+                        Some(parent) => parent,
+                        None => return Err(Error::UnbalancedWhere { context: ErrorContext::identifier(")") }),
+                    };
+                    let closed = std::mem::replace(&mut group, parent);
+                    if !closed.query.is_empty() {
+                        group.push_connector(closed.connector);
+                        group.query.push_str(&format!("({})", closed.query));
+                        group.parameters_loc.extend(closed.parameters_loc);
+                    }
+                },
+                WhereOperator::Error(e) => return Err(e.clone()),
             }
         }
+        if !parents.is_empty() {
+            return Err(Error::UnbalancedWhere { context: ErrorContext::identifier("(") });
+        }
         Ok(QueryWithParamsLoc {
-            query: query,
-            parameters_loc: parameters,
+            query: group.query,
+            parameters_loc: group.parameters_loc,
         })
     }
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    pub fn parameter_len(&self) -> usize {
+        self.0.iter().map(|wo| match wo {
+            WhereOperator::ParameterLoc(_) => 1,
+            WhereOperator::SubQuery(sub) => sub.parameter_len(),
+            _ => 0,
+        }).sum()
+    }
     pub fn push(&mut self, field: WhereOperator) {
         self.0.push(field);
     }
@@ -71,3 +126,32 @@ impl QueryWheres {
         self.0.extend(qwhere.0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn build(ops: Vec<WhereOperator>) -> Result<String, Error> {
+        QueryWheres(ops).build(&mut ParamIter::default()).map(|built| built.query)
+    }
+    fn sql(s: &str) -> WhereOperator {
+        WhereOperator::Sql(s.to_owned())
+    }
+    #[test]
+    fn test_where_group() {
+        use WhereOperator::{And, Or, Open, Close, ParameterLoc};
+        assert_eq!(build(vec![sql("a = "), ParameterLoc(0), And, Open, sql("b = "), ParameterLoc(1), Or, sql("c")]).unwrap_err(), Error::UnbalancedWhere { context: ErrorContext::identifier("(") });
+        assert_eq!(build(vec![sql("a = "), ParameterLoc(0), And, Open, sql("b = "), ParameterLoc(1), Or, sql("c"), Close]).unwrap(), "a = $1 AND (b = $2 OR c)");
+        // the connector of the first condition inside a group is dropped
+        assert_eq!(build(vec![Open, And, sql("a"), Or, sql("b"), Close, And, sql("c")]).unwrap(), "(a OR b) AND c");
+        assert_eq!(build(vec![sql("a"), Or, Open, And, Open, Or, sql("b"), Close, Close]).unwrap(), "a OR ((b))");
+    }
+    #[test]
+    fn test_where_empty_group() {
+        use WhereOperator::{And, Or, Open, Close};
+        assert_eq!(build(vec![sql("a"), And, Open, Close, Or, sql("b")]).unwrap(), "a OR b");
+        assert_eq!(build(vec![Open, Close, And, sql("a")]).unwrap(), "a");
+        assert_eq!(build(vec![Open, And, Open, Close, Close]).unwrap(), "");
+        assert!(matches!(build(vec![sql("a"), Close]), Err(Error::UnbalancedWhere { .. })));
+        assert!(matches!(build(vec![Open, Close, Close, Open]), Err(Error::UnbalancedWhere { .. })));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::error::Error;
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter};
+use crate::postgres::query_token::QueryTokens;
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryWindows(Vec<QueryWindow>);
+
+impl QueryWindows {
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for window in &self.0 {
+            let built = window.spec.build(i)?;
+            v.push(format!("{} AS ({})", window.name, built.query));
+            p.extend(built.parameters_loc);
+        }
+        Ok(QueryWithParamsLoc {
+            query: v.join(", "),
+            parameters_loc: p,
+        })
+    }
+    pub fn push(&mut self, window: QueryWindow) {
+        self.0.push(window);
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryWindow {
+    pub name: String,
+    pub spec: QueryTokens,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_window_1() {
+        let mut windows = QueryWindows::default();
+        windows.push(QueryWindow {
+            name: "w".to_owned(),
+            spec: "PARTITION BY team_id".into(),
+        });
+        windows.push(QueryWindow {
+            name: "w_score".to_owned(),
+            spec: "w ORDER BY score DESC".into(),
+        });
+        assert_eq!(windows.build(&mut ParamIter::default()).unwrap().query, "w AS (PARTITION BY team_id), w_score AS (w ORDER BY score DESC)");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::Error;
+use crate::postgres::query_field::{QueryWithParamsLoc, ParamIter, ParameterValue};
+use crate::postgres::query_token::{QueryTokens};
+use crate::postgres::query_subquery::{SubQuery};
+use crate::postgres::ident::Alias;
+
+#[derive(Clone, Debug)]
+pub struct QueryWith {
+    pub name: String,
+    pub materialized: Option<bool>,
+    pub body: QueryTokens,
+}
+
+impl QueryWith {
+    fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let built = self.body.build(i)?;
+        let materialized = match self.materialized {
+            Some(true) => "MATERIALIZED ",
+            Some(false) => "NOT MATERIALIZED ",
+            None => "",
+        };
+        Ok(QueryWithParamsLoc {
+            query: format!("{} AS {}({})", self.name, materialized, built.query),
+            parameters_loc: built.parameters_loc,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryWiths {
+    recursive: bool,
+    withs: Vec<QueryWith>,
+}
+
+impl QueryWiths {
+    pub fn build(&self, i: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let mut v: Vec<String> = Vec::new();
+        let mut p: Vec<usize> = Vec::new();
+        for with in &self.withs {
+            let built = with.build(i)?;
+            v.push(built.query);
+            p.extend(built.parameters_loc);
+        }
+        let recursive = if self.recursive { "RECURSIVE " } else { "" };
+        Ok(QueryWithParamsLoc {
+            query: format!("WITH {}{}", recursive, v.join(", ")),
+            parameters_loc: p,
+        })
+    }
+    /// the parameters of the body are moved into the parameters of the statement
+    pub fn push<'a>(&mut self, name: String, sub: SubQuery<'a>, materialized: Option<bool>, parameters: &mut Vec<ParameterValue<'a>>) {
+        let len = parameters.len();
+        let (tokens, sub_parameters) = sub.into_format_query();
+        parameters.extend(sub_parameters);
+        self.withs.push(QueryWith {
+            name,
+            materialized,
+            body: tokens.into_query_tokens(len),
+        });
+    }
+    pub fn set_recursive(&mut self) {
+        self.recursive = true;
+    }
+    pub fn len(&self) -> usize {
+        self.withs.len()
+    }
+    pub fn parameter_len(&self) -> usize {
+        self.withs.iter().map(|with| with.body.parameter_len()).sum()
+    }
+}
+
+/// the WITH clause of select, insert, update and delete, import it to call with() on a builder
+pub trait WithQuery<'a> {
+    #[doc(hidden)]
+    fn withs_mut(&mut self) -> (&mut QueryWiths, &mut Vec<ParameterValue<'a>>);
+    /// WITH name AS (...), the body can be any of the builders or a built query
+    fn with<S: Into<Alias>, T: Into<SubQuery<'a>>>(&mut self, name: S, sub: T) -> &mut Self {
+        let (withs, parameters) = self.withs_mut();
+        withs.push(name.into().to_sql(), sub.into(), None, parameters);
+        self
+    }
+    /// WITH name AS MATERIALIZED (...)
+    fn with_materialized<S: Into<Alias>, T: Into<SubQuery<'a>>>(&mut self, name: S, sub: T) -> &mut Self {
+        let (withs, parameters) = self.withs_mut();
+        withs.push(name.into().to_sql(), sub.into(), Some(true), parameters);
+        self
+    }
+    /// WITH name AS NOT MATERIALIZED (...)
+    fn with_not_materialized<S: Into<Alias>, T: Into<SubQuery<'a>>>(&mut self, name: S, sub: T) -> &mut Self {
+        let (withs, parameters) = self.withs_mut();
+        withs.push(name.into().to_sql(), sub.into(), Some(false), parameters);
+        self
+    }
+    /// WITH RECURSIVE name AS (...), RECURSIVE applies to the whole WITH clause
+    fn with_recursive<S: Into<Alias>, T: Into<SubQuery<'a>>>(&mut self, name: S, sub: T) -> &mut Self {
+        let (withs, parameters) = self.withs_mut();
+        withs.set_recursive();
+        withs.push(name.into().to_sql(), sub.into(), None, parameters);
+        self
+    }
+}
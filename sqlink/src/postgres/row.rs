@@ -0,0 +1,23 @@
+use postgres_types::FromSql;
+
+/// a row that can be read by column name, the row type of `#[derive(SqlRow)]`'s `from_row`
+///
+/// enable the `tokio-postgres` feature to use it with `tokio_postgres::Row`, which is also `postgres::Row`,
+/// or implement it for the row type of another client
+pub trait RowGet {
+    /// panic when the column does not exist or has a different type, same as `Row::get`
+    fn get_column<'b, T: FromSql<'b>>(&'b self, column: &str) -> T;
+}
+
+#[cfg(feature = "tokio-postgres")]
+impl RowGet for tokio_postgres::Row {
+    fn get_column<'b, T: FromSql<'b>>(&'b self, column: &str) -> T {
+        self.get(column)
+    }
+}
+
+impl<R: RowGet> RowGet for &R {
+    fn get_column<'b, T: FromSql<'b>>(&'b self, column: &str) -> T {
+        (**self).get_column(column)
+    }
+}
@@ -1,76 +1,204 @@
-use crate::error::Error;
-use crate::postgres::query_limit_offset::QueryLimitOffset;
+use std::convert::TryInto;
+use crate::error::{Error, ErrorContext, BuilderKind, Clause};
+use crate::postgres::query_limit_offset::{QueryLimitOffset, LimitOffset};
+use crate::postgres::query_lock::{QueryLocks, Lock};
 use crate::postgres::query_where::{QueryWheres, WhereOperator};
 use crate::postgres::query_group::{QueryGroups, QueryGroup};
-use crate::postgres::query_order::{QueryOrders, QueryOrder};
+use crate::postgres::query_order::{QueryOrders, QueryOrder, OrderWay, Order};
+use crate::postgres::query_distinct::QueryDistinct;
+use crate::postgres::query_window::{QueryWindows, QueryWindow};
 use crate::postgres::query_table::{QueryTables, QueryTable};
 use crate::postgres::query_select::{QuerySelects, QuerySelectField};
-use crate::postgres::query_field::{QueryWithParams, ParameterValueAsRef};
-use crate::postgres::query_token::FormatQueryTup;
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParameterValue, ParamIter};
+use crate::postgres::expr::{Expr, Window, bind, tuple};
+use crate::postgres::query_token::{QueryTokens, FormatQueryTup};
+use crate::postgres::query_subquery::{SubQuery, subquery};
+use crate::postgres::query_with::{QueryWiths, WithQuery};
+use crate::postgres::ident::Alias;
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct SqlSelect<'a> {
+    _withs: QueryWiths,
     _tables: QueryTables, // to support update tableA, tableB set ...
     _wheres: QueryWheres,
+    _distinct: Option<QueryDistinct>,
     _selects: QuerySelects,
     _orders: QueryOrders,
     _groups: QueryGroups,
+    _havings: QueryWheres,
+    _windows: QueryWindows,
     _limit_offset: Option<QueryLimitOffset>,
-    _parameters: Vec<ParameterValueAsRef<'a>>,
+    _locks: QueryLocks,
+    _parameters: Vec<ParameterValue<'a>>,
 }
 
 impl<'a> SqlSelect<'a> {
     pub fn new() -> SqlSelect<'static> {
         SqlSelect::default()
     }
-    pub fn build(&self) -> Result<QueryWithParams, Error> {
-        let mut param_iter = 1;
-        let built_for_select: String = self._selects.build()?;
-        let built_for_table = self._tables.build(&mut param_iter)?;
+    pub fn build(&self) -> Result<QueryWithParams<'a>, Error> {
+        let mut param_iter = ParamIter::default();
+        let built = self.build_loc(&mut param_iter)?;
+        Ok(QueryWithParams {
+            query: built.query,
+            parameters: built.parameters_loc.into_iter().map(|ploc| self._parameters[ploc].clone()).collect(),
+        })
+    }
+    pub(crate) fn build_loc(&self, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let at = |clause: Clause| move |e: Error| e.within(BuilderKind::Select, clause);
         let mut vec: Vec<String> = Vec::new();
-        let mut p: Vec<ParameterValueAsRef> = Vec::new();
-        vec.push(format!("SELECT {} FROM {}", built_for_select, built_for_table.query));
-        for ploc in built_for_table.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let mut p: Vec<usize> = Vec::new();
+        if self._withs.len() > 0 {
+            let built_for_with = self._withs.build(param_iter).map_err(at(Clause::With))?;
+            vec.push(built_for_with.query);
+            p.extend(built_for_with.parameters_loc);
         }
-        if self._wheres.len() > 0 {
-            let built_for_where = self._wheres.build(&mut param_iter)?;
+        if let Some(distinct) = &self._distinct {
+            let built_for_distinct = distinct.build(param_iter).map_err(at(Clause::Select))?;
+            vec.push(format!("SELECT {}", built_for_distinct.query));
+            p.extend(built_for_distinct.parameters_loc);
+        } else {
+            vec.push("SELECT".to_owned());
+        }
+        let built_for_select = self._selects.build(param_iter).map_err(at(Clause::Select))?;
+        let built_for_table = self._tables.build(param_iter).map_err(at(Clause::From))?;
+        vec.push(format!("{} FROM {}", built_for_select.query, built_for_table.query));
+        p.extend(built_for_select.parameters_loc);
+        p.extend(built_for_table.parameters_loc);
+        let built_for_where = self._wheres.build(param_iter).map_err(at(Clause::Where))?;
+        if !built_for_where.query.is_empty() {
             vec.push(format!("WHERE {}", built_for_where.query));
-            for ploc in built_for_where.parameters_loc {
-                p.push(self._parameters[ploc]);
-            }
+            p.extend(built_for_where.parameters_loc);
         }
         if self._groups.len() > 0 {
-            let group: String = self._groups.build()?;
-            vec.push(format!("GROUP BY {}", group));
+            let built_for_group = self._groups.build(param_iter).map_err(at(Clause::GroupBy))?;
+            vec.push(format!("GROUP BY {}", built_for_group.query));
+            p.extend(built_for_group.parameters_loc);
+        }
+        let built_for_having = self._havings.build(param_iter).map_err(at(Clause::Having))?;
+        if !built_for_having.query.is_empty() {
+            vec.push(format!("HAVING {}", built_for_having.query));
+            p.extend(built_for_having.parameters_loc);
+        }
+        if self._windows.len() > 0 {
+            let built_for_window = self._windows.build(param_iter).map_err(at(Clause::Window))?;
+            vec.push(format!("WINDOW {}", built_for_window.query));
+            p.extend(built_for_window.parameters_loc);
         }
         if self._orders.len() > 0 {
-            let order: String = self._orders.build()?;
-            vec.push(format!("ORDER BY {}", order));
+            let built_for_order = self._orders.build(param_iter).map_err(at(Clause::OrderBy))?;
+            vec.push(format!("ORDER BY {}", built_for_order.query));
+            p.extend(built_for_order.parameters_loc);
+        }
+        // after the ORDER BY is built, so a bad direction is reported before a DISTINCT ON mismatch
+        if let Some(distinct) = &self._distinct {
+            distinct.check_order(&self._orders).map_err(at(Clause::OrderBy))?;
         }
         if let Some(limitoffset) = &self._limit_offset {
-            vec.push(limitoffset.build()?);
+            if limitoffset.with_ties() && self._orders.len() == 0 {
+                return Err(Error::Syntax {
+                    message: "WITH TIES requires ORDER BY".to_owned(),
+                    context: ErrorContext::default(),
+                }).map_err(at(Clause::Limit));
+            }
+            let built_for_limit = limitoffset.build(param_iter).map_err(at(Clause::Limit))?;
+            vec.push(built_for_limit.query);
+            p.extend(built_for_limit.parameters_loc);
         }
-        Ok(QueryWithParams {
+        if self._locks.len() > 0 {
+            let conflict = if self._distinct.is_some() {
+                Some("DISTINCT")
+            } else if self._groups.len() > 0 {
+                Some("GROUP BY")
+            } else if self._havings.len() > 0 {
+                Some("HAVING")
+            } else if self._windows.len() > 0 {
+                Some("WINDOW")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(Error::LockNotAllowed { context: ErrorContext::identifier(conflict) }).map_err(at(Clause::Lock));
+            }
+            vec.push(self._locks.build().map_err(at(Clause::Lock))?);
+        }
+        Ok(QueryWithParamsLoc {
             query: vec.join(" "),
-            parameters: p,
+            parameters_loc: p,
         })
     }
+    pub(crate) fn into_parts(self) -> (SqlSelect<'static>, Vec<ParameterValue<'a>>) {
+        let select = SqlSelect {
+            _withs: self._withs,
+            _tables: self._tables,
+            _wheres: self._wheres,
+            _distinct: self._distinct,
+            _selects: self._selects,
+            _orders: self._orders,
+            _groups: self._groups,
+            _havings: self._havings,
+            _windows: self._windows,
+            _limit_offset: self._limit_offset,
+            _locks: self._locks,
+            _parameters: Vec::new(),
+        };
+        (select, self._parameters)
+    }
+    /// None when a * is selected, as the column count is only known by postgres
+    pub(crate) fn column_count(&self) -> Option<usize> {
+        self._selects.column_count()
+    }
     pub fn reset_selects(&mut self) -> &mut Self {
         self._selects = QuerySelects::default();
         self
     }
-    pub fn select<S: Into<String>>(&mut self, field: S) -> &mut Self {
+    /// COUNT(*) of the same rows for pagination, ORDER BY, LIMIT / OFFSET and the locks are dropped,
+    /// a select with DISTINCT, GROUP BY or HAVING is counted as a subquery so each distinct row or group is 1 row
+    pub fn count(&self) -> SqlSelect<'a> {
+        let mut rows = self.clone();
+        rows._orders = QueryOrders::default();
+        rows._limit_offset = None;
+        rows._locks = QueryLocks::default();
+        if rows._distinct.is_none() && rows._groups.len() == 0 && rows._havings.len() == 0 {
+            rows.reset_selects().select("COUNT(*)");
+            return rows;
+        }
+        let mut count = SqlSelect::default();
+        count.select("COUNT(*)").table_subquery(rows, "count_rows");
+        count
+    }
+    pub fn distinct(&mut self) -> &mut Self {
+        self._distinct = Some(QueryDistinct::All);
+        self
+    }
+    /// DISTINCT ON (...), the leading ORDER BY expressions must be the same expressions, the build fails otherwise
+    pub fn distinct_on<I: IntoIterator<Item = E>, E: Into<Expr<'a>>>(&mut self, fields: I) -> &mut Self {
+        let qtokens = fields.into_iter().map(|field| self.push_format_query(field.into().into_format_query())).collect();
+        self._distinct = Some(QueryDistinct::On(qtokens));
+        self
+    }
+    pub fn select<E: Into<Expr<'a>>>(&mut self, field: E) -> &mut Self {
+        let qtokens = self.push_format_query(field.into().into_format_query());
         self._selects.push(QuerySelectField {
-            name: field.into(),
+            name: qtokens,
             alias: None
         });
         self
     }
-    pub fn select_as<S: Into<String>, T: Into<String>>(&mut self, field: S, alias: T) -> &mut Self {
+    pub fn select_as<E: Into<Expr<'a>>, T: Into<Alias>>(&mut self, field: E, alias: T) -> &mut Self {
+        let qtokens = self.push_format_query(field.into().into_format_query());
         self._selects.push(QuerySelectField {
-            name: field.into(),
-            alias: Some(alias.into())
+            name: qtokens,
+            alias: Some(alias.into().to_sql())
+        });
+        self
+    }
+    /// (SELECT ...) AS alias, a scalar subquery in the select list
+    pub fn select_subquery_as<S: Into<SubQuery<'a>>, T: Into<Alias>>(&mut self, sub: S, alias: T) -> &mut Self {
+        let qtokens = self.push_format_query(subquery(sub));
+        self._selects.push(QuerySelectField {
+            name: qtokens,
+            alias: Some(alias.into().to_sql())
         });
         self
     }
@@ -78,39 +206,106 @@ impl<'a> SqlSelect<'a> {
         self._tables.push(table.into());
         self
     }
-    pub fn order<S: Into<String>, T: Into<String>>(&mut self, field: S, order_way: T) -> &mut Self {
-        self._orders.push(QueryOrder {
-            name: format!("{} {}", field.into(), order_way.into()),
-        });
+    /// FROM (SELECT ...) AS alias
+    pub fn table_subquery<S: Into<SubQuery<'a>>, T: Into<Alias>>(&mut self, sub: S, alias: T) -> &mut Self {
+        let query_table = self.subquery_table(sub, alias);
+        self._tables.push(query_table);
         self
     }
-    pub fn group<S: Into<String>>(&mut self, field: S) -> &mut Self {
+    fn push_format_query(&mut self, ftup: FormatQueryTup<'a>) -> QueryTokens {
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        (ftup.0).into_query_tokens(len)
+    }
+    /// order_way is Order::Asc, Order::Desc.nulls_last(), etc, a string is parsed and any other direction fails the build
+    pub fn order<E: Into<Expr<'a>>, O>(&mut self, field: E, order_way: O) -> &mut Self where O: TryInto<OrderWay>, O::Error: Into<Error> {
+        let qtokens = self.push_format_query(field.into().into_format_query());
+        self._orders.push(QueryOrder::new(qtokens, order_way));
+        self
+    }
+    pub fn group<E: Into<Expr<'a>>>(&mut self, field: E) -> &mut Self {
+        let qtokens = self.push_format_query(field.into().into_format_query());
         self._groups.push(QueryGroup {
-            name: field.into(),
+            name: qtokens,
+        });
+        self
+    }
+    /// WINDOW name AS (...), used by expr.over_named(name) or window().base(name)
+    pub fn window<S: Into<Alias>>(&mut self, name: S, window: Window<'a>) -> &mut Self {
+        let spec = self.push_format_query(window.into_format_query());
+        self._windows.push(QueryWindow {
+            name: name.into().to_sql(),
+            spec,
         });
         self
     }
-    pub fn limit_offset<S: Into<QueryLimitOffset>>(&mut self, limit_offset: S) -> &mut Self {
-        self._limit_offset = Some(limit_offset.into());
+    /// a usize, (limit, offset), or LimitOffset for bind parameters, LIMIT ALL, OFFSET only and FETCH FIRST
+    pub fn limit_offset<S: Into<LimitOffset<'a>>>(&mut self, limit_offset: S) -> &mut Self {
+        self._limit_offset = Some(limit_offset.into().into_query(&mut self._parameters));
         self
     }
-    pub fn and_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    /// keyset pagination, the rows after the given key values in the order of the keys, then ORDER BY the keys and LIMIT,
+    /// the keys should end with a unique column and be NOT NULL, the values of the last row can be kept in a Cursor.
+    /// when every key has the same order it is a row comparison (a, b) > ($1, $2),
+    /// otherwise it is expanded into a > $1 OR a = $2 AND b < $3
+    pub fn seek_after<E, P>(&mut self, keys: &[(E, Order, P)], limit: usize) -> &mut Self
+        where E: Into<Expr<'a>> + Clone, P: Into<ParameterValue<'a>> + Clone {
+        let compare = |field: Expr<'a>, order: Order, value: Expr<'a>| match order {
+            Order::Asc => field.gt(value),
+            Order::Desc => field.lt(value),
+        };
+        let cond = if keys.len() == 1 {
+            let (field, order, value) = keys[0].clone();
+            Some(compare(field.into(), order, bind(value)))
+        } else if keys.len() > 1 && keys.iter().all(|key| key.1 == keys[0].1) {
+            let fields = keys.iter().map(|key| key.0.clone().into()).collect();
+            let values = keys.iter().map(|key| bind(key.2.clone())).collect();
+            Some(compare(tuple(fields), keys[0].1, tuple(values)))
+        } else {
+            (0..keys.len()).map(|idx| {
+                let (field, order, value) = keys[idx].clone();
+                keys[..idx].iter()
+                    .map(|key| key.0.clone().into().eq(bind(key.2.clone())))
+                    .chain(std::iter::once(compare(field.into(), order, bind(value))))
+                    .reduce(|acc, expr| acc.and(expr))
+                    .unwrap()
+            }).reduce(|acc, expr| acc.or(expr))
+        };
+        if let Some(cond) = cond {
+            self.and_where(cond);
+        }
+        for (field, order, _) in keys {
+            self.order(field.clone(), *order);
+        }
+        self.limit_offset(limit)
+    }
+    /// FOR UPDATE / FOR SHARE ..., written after LIMIT, a select can have more than 1 lock
+    pub fn lock(&mut self, lock: Lock) -> &mut Self {
+        self._locks.push(lock);
+        self
+    }
+    pub(crate) fn has_lock(&self) -> bool {
+        self._locks.len() > 0
+    }
+    pub fn and_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::And);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
-    pub fn or_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn or_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::Or);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
@@ -130,47 +325,135 @@ impl<'a> SqlSelect<'a> {
     }
     pub fn where_close(&mut self) -> &mut Self {
         self._wheres.push(WhereOperator::Close);
-        // lets dont panic, if error database will panic
         self
     }
-    pub fn inner_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn and_having<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
+        if self._havings.len() > 0 {
+            self._havings.push(WhereOperator::And);
+        }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._havings.extend(qtokens.into());
+        self
+    }
+    pub fn or_having<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
+        if self._havings.len() > 0 {
+            self._havings.push(WhereOperator::Or);
+        }
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._havings.extend(qtokens.into());
+        self
+    }
+    pub fn and_having_open(&mut self) -> &mut Self {
+        if self._havings.len() > 0 {
+            self._havings.push(WhereOperator::And);
+        }
+        self._havings.push(WhereOperator::Open);
+        self
+    }
+    pub fn or_having_open(&mut self) -> &mut Self {
+        if self._havings.len() > 0 {
+            self._havings.push(WhereOperator::Or);
+        }
+        self._havings.push(WhereOperator::Open);
+        self
+    }
+    pub fn having_close(&mut self) -> &mut Self {
+        self._havings.push(WhereOperator::Close);
+        self
+    }
+    pub fn inner_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
+        let len = self._parameters.len();
+        self._parameters.extend(ftup.1);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._tables.inner_join(query_table.into());
         self._tables.on(qtokens.into());
         self
     }
-    pub fn left_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn left_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._tables.left_join(query_table.into());
         self._tables.on(qtokens.into());
         self
     }
-    pub fn right_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn right_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._tables.right_join(query_table.into());
         self._tables.on(qtokens.into());
         self
     }
-    pub fn full_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn full_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._tables.full_join(query_table.into());
         self._tables.on(qtokens.into());
         self
     }
+    fn subquery_table<S: Into<SubQuery<'a>>, T: Into<Alias>>(&mut self, sub: S, alias: T) -> QueryTable {
+        let qtokens = self.push_format_query(sub.into().into_format_query());
+        QueryTable::subquery(qtokens, alias.into().to_sql())
+    }
+    pub fn inner_join_subquery<S: Into<SubQuery<'a>>, T: Into<Alias>, E: Into<Expr<'a>>>(&mut self, sub: S, alias: T, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
+        let query_table = self.subquery_table(sub, alias);
+        let qtokens = self.push_format_query(ftup);
+        self._tables.inner_join(query_table);
+        self._tables.on(qtokens.into());
+        self
+    }
+    pub fn left_join_subquery<S: Into<SubQuery<'a>>, T: Into<Alias>, E: Into<Expr<'a>>>(&mut self, sub: S, alias: T, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
+        let query_table = self.subquery_table(sub, alias);
+        let qtokens = self.push_format_query(ftup);
+        self._tables.left_join(query_table);
+        self._tables.on(qtokens.into());
+        self
+    }
+    pub fn right_join_subquery<S: Into<SubQuery<'a>>, T: Into<Alias>, E: Into<Expr<'a>>>(&mut self, sub: S, alias: T, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
+        let query_table = self.subquery_table(sub, alias);
+        let qtokens = self.push_format_query(ftup);
+        self._tables.right_join(query_table);
+        self._tables.on(qtokens.into());
+        self
+    }
+    pub fn full_join_subquery<S: Into<SubQuery<'a>>, T: Into<Alias>, E: Into<Expr<'a>>>(&mut self, sub: S, alias: T, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
+        let query_table = self.subquery_table(sub, alias);
+        let qtokens = self.push_format_query(ftup);
+        self._tables.full_join(query_table);
+        self._tables.on(qtokens.into());
+        self
+    }
+}
+
+impl<'a> WithQuery<'a> for SqlSelect<'a> {
+    fn withs_mut(&mut self) -> (&mut QueryWiths, &mut Vec<ParameterValue<'a>>) {
+        (&mut self._withs, &mut self._parameters)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::postgres::query_token::{format_query};
+    use crate::postgres::query_token::{format_query, compose_query};
+    use crate::postgres::op;
+    use crate::postgres::query_field::ParameterValueAsRef;
     use super::*;
+    use crate::error::ErrorContext;
     #[test]
     fn test_select_builder_1() {
         let mut sqlselect = SqlSelect::new();
@@ -187,4 +470,608 @@ mod tests {
         assert_eq!(qbuild.query, "SELECT u.username, u.user_id AS uid FROM \"user\" AS u LEFT JOIN \"user_detail\" AS ud ON u.user_id = ud.user_id AND ud.code = $1 WHERE user.user_id = $2 GROUP BY something ORDER BY user.created_at DESC LIMIT 10 OFFSET 20");
         // assert_eq!(qbuild.parameters, vec![ParameterValue::I32(2), ParameterValue::I32(1)]);
     }
+
+    #[test]
+    fn test_select_builder_subquery() {
+        let mut sub_where = SqlSelect::new();
+        sub_where
+            .select("user_id")
+            .table("user_bank")
+            .and_where(format_query("code = {}", vec![&"abc"]));
+        let mut sub_count = SqlSelect::new();
+        sub_count
+            .select("COUNT(*)")
+            .table(("order", "o"))
+            .and_where(format_query("o.user_id = u.id AND o.status = {}", vec![&1]));
+        let mut sub_exists = SqlSelect::new();
+        sub_exists
+            .select("1")
+            .table("ban")
+            .and_where(format_query("ban.user_id = u.id AND ban.level > {}", vec![&2]));
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("u.id")
+            .select_subquery_as(sub_count, "order_count")
+            .table(("user", "u"))
+            .and_where(format_query("u.age > {}", vec![&18]))
+            .and_where(op::in_select("u.id", sub_where))
+            .and_where(op::not_exists(sub_exists))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT u.id, (SELECT COUNT(*) FROM \"order\" AS o WHERE o.user_id = u.id AND o.status = $1) AS order_count FROM \"user\" AS u WHERE u.age > $2 AND u.id IN (SELECT user_id FROM \"user_bank\" WHERE code = $3) AND NOT EXISTS (SELECT 1 FROM \"ban\" WHERE ban.user_id = u.id AND ban.level > $4)");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 18, \"abc\", 2]");
+    }
+
+    #[test]
+    fn test_select_builder_subquery_table() {
+        let mut sub_table = SqlSelect::new();
+        sub_table
+            .select("user_id")
+            .select_as("SUM(amount)", "total")
+            .table("payment")
+            .and_where(format_query("status = {}", vec![&"paid"]))
+            .group("user_id");
+        let mut sub_join = SqlSelect::new();
+        sub_join
+            .select("user_id")
+            .select("name")
+            .table("profile")
+            .and_where(format_query("visible = {}", vec![&true]));
+        let built_join = sub_join.build().unwrap();
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("p.user_id")
+            .select("pr.name")
+            .table_subquery(sub_table, "p")
+            .left_join_subquery(built_join, "pr", format_query("pr.user_id = p.user_id AND pr.name <> {}", vec![&""]))
+            .and_where(format_query("p.total > {}", vec![&100]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT p.user_id, pr.name FROM (SELECT user_id, SUM(amount) AS total FROM \"payment\" WHERE status = $1 GROUP BY user_id) AS p LEFT JOIN (SELECT user_id, name FROM \"profile\" WHERE visible = $2) AS pr ON pr.user_id = p.user_id AND pr.name <> $3 WHERE p.total > $4");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"paid\", true, \"\", 100]");
+    }
+
+    #[test]
+    fn test_select_builder_nested_subquery() {
+        let mut inner = SqlSelect::new();
+        inner
+            .select("id")
+            .table("c")
+            .and_where(format_query("x = {}", vec![&3]));
+        let mut middle = SqlSelect::new();
+        middle
+            .select("id")
+            .table("b")
+            .and_where(format_query("y = {}", vec![&2]))
+            .and_where(op::in_select("id", inner));
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("a")
+            .and_where(format_query("z = {}", vec![&1]))
+            .and_where(compose_query("id IN {} OR id = {}", vec![subquery(middle), format_query("{}", vec![&4])]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"a\" WHERE z = $1 AND id IN (SELECT id FROM \"b\" WHERE y = $2 AND id IN (SELECT id FROM \"c\" WHERE x = $3)) OR id = $4");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 2, 3, 4]");
+    }
+
+    #[test]
+    fn test_select_builder_with() {
+        let mut active = SqlSelect::new();
+        active
+            .select("id")
+            .table("user")
+            .and_where(format_query("status = {}", vec![&"active"]));
+        let mut paid = SqlSelect::new();
+        paid
+            .select("user_id")
+            .table("payment")
+            .and_where(format_query("amount > {}", vec![&10]));
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .with("active_user", active)
+            .with_not_materialized("paid_user", paid)
+            .select("a.id")
+            .table(("active_user", "a"))
+            .inner_join(("paid_user", "p"), format_query("p.user_id = a.id AND a.id > {}", vec![&0]))
+            .and_where(format_query("a.id < {}", vec![&100]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH active_user AS (SELECT id FROM \"user\" WHERE status = $1), paid_user AS NOT MATERIALIZED (SELECT user_id FROM \"payment\" WHERE amount > $2) SELECT a.id FROM \"active_user\" AS a INNER JOIN \"paid_user\" AS p ON p.user_id = a.id AND a.id > $3 WHERE a.id < $4");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"active\", 10, 0, 100]");
+    }
+
+    #[test]
+    fn test_select_builder_with_recursive() {
+        let tree = QueryWithParams {
+            query: "SELECT id, parent_id FROM category WHERE id = $1 UNION ALL SELECT c.id, c.parent_id FROM category c INNER JOIN tree t ON c.parent_id = t.id".to_owned(),
+            parameters: vec![ParameterValue::owned(1)],
+        };
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .with_recursive("tree(id, parent_id)", tree)
+            .select("id")
+            .table("tree")
+            .and_where(format_query("id <> {}", vec![&2]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH RECURSIVE tree(id, parent_id) AS (SELECT id, parent_id FROM category WHERE id = $1 UNION ALL SELECT c.id, c.parent_id FROM category c INNER JOIN tree t ON c.parent_id = t.id) SELECT id FROM \"tree\" WHERE id <> $2");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 2]");
+    }
+
+    #[test]
+    fn test_select_builder_many_parameters() {
+        let id = 1;
+        let in_list = |n: usize| format!("id IN ({})", vec!["{}"; n].join(","));
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("user")
+            .and_where(format_query(in_list(200), vec![&id as ParameterValueAsRef; 200]))
+            .build().unwrap();
+        assert_eq!(qbuild.parameters.len(), 200);
+        assert!(qbuild.query.ends_with("$127,$128,$129,$130,$131,$132,$133,$134,$135,$136,$137,$138,$139,$140,$141,$142,$143,$144,$145,$146,$147,$148,$149,$150,$151,$152,$153,$154,$155,$156,$157,$158,$159,$160,$161,$162,$163,$164,$165,$166,$167,$168,$169,$170,$171,$172,$173,$174,$175,$176,$177,$178,$179,$180,$181,$182,$183,$184,$185,$186,$187,$188,$189,$190,$191,$192,$193,$194,$195,$196,$197,$198,$199,$200)"));
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select("id")
+            .table("user")
+            .and_where(format_query(in_list(65535), vec![&id as ParameterValueAsRef; 65535]));
+        assert_eq!(sqlselect.build().unwrap().parameters.len(), 65535);
+        sqlselect.and_where(format_query("id <> {}", vec![&id]));
+        assert!(matches!(sqlselect.build(), Err(Error::ParameterOverflow { max: 65535, .. })));
+    }
+
+    #[test]
+    fn test_select_builder_deferred_error() {
+        let id = 1;
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select("id")
+            .table("user")
+            .and_where(format_query("id = {} OR id = {}", vec![&id]))
+            .and_where(format_query("name = {}", vec![&id]));
+        assert_eq!(sqlselect.build().unwrap_err(), Error::ArgumentCountMismatch {
+            expected: 2,
+            given: 1,
+            context: ErrorContext {
+                builder: Some(BuilderKind::Select),
+                clause: Some(Clause::Where),
+                identifier: Some("id = {} OR id = {}".to_owned()),
+            },
+        });
+
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select("id")
+            .table("user")
+            .and_where(compose_query("id IN {}", vec![]));
+        assert!(matches!(sqlselect.build(), Err(Error::ArgumentCountMismatch { expected: 1, given: 0, .. })));
+
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select("id")
+            .inner_join(("person", "p"), format_query("p.user_id = u.id", vec![]))
+            .table(("user", "u"));
+        let error = sqlselect.build().unwrap_err();
+        assert!(matches!(error, Error::MissingTable { .. }));
+        assert_eq!(error.to_string(), "no table is given [builder: SELECT, clause: JOIN, identifier: person]");
+    }
+
+    #[test]
+    fn test_select_builder_where_group() {
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("user")
+            .and_where_open()
+            .and_where(format_query("name = {}", vec![&"foo"]))
+            .or_where(format_query("name = {}", vec![&"bar"]))
+            .where_close()
+            .and_where_open()
+            .where_close()
+            .and_where(op::gt("age", &18))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"user\" WHERE (name = $1 OR name = $2) AND age > $3");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"foo\", \"bar\", 18]");
+
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("user")
+            .or_where_open()
+            .where_close()
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"user\"");
+
+        sqlselect.where_close();
+        let error = sqlselect.build().unwrap_err();
+        assert!(matches!(error, Error::UnbalancedWhere { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Where));
+    }
+
+    #[test]
+    fn test_select_builder_expr() {
+        use crate::postgres::expr::{col, bind, lit, func, case};
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select(col("u.team_id"))
+            .select_as(func("SUM", vec![case().when(col("u.age").gte(bind(&18)), lit(1)).otherwise(lit(0)).end()]), "adults")
+            .table(("user", "u"))
+            .and_where(col("u.name").ilike(bind(&"a%")).or(col("u.name").is_null()))
+            .and_where(op::gt("u.id", &10))
+            .group(col("u.team_id"))
+            .order(func("COALESCE", vec![col("u.team_id"), bind(&0)]), "DESC")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT \"u\".\"team_id\", SUM(CASE WHEN \"u\".\"age\" >= $1 THEN 1 ELSE 0 END) AS adults FROM \"user\" AS u WHERE (\"u\".\"name\" ILIKE $2 OR \"u\".\"name\" IS NULL) AND u.id > $3 GROUP BY \"u\".\"team_id\" ORDER BY COALESCE(\"u\".\"team_id\", $4) DESC");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[18, \"a%\", 10, 0]");
+    }
+
+    #[test]
+    fn test_select_builder_condition() {
+        use crate::postgres::condition::{Condition, and, or, not};
+        use crate::postgres::expr::{col, bind};
+        // a filter tree built apart from the statement, as a filter ui would
+        let filters = [("name", "a%"), ("email", "b%")];
+        let filter = or(filters.iter().map(|(field, value)| col(*field).like(bind(value))).collect());
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("u.id")
+            .table(("user", "u"))
+            .left_join(("team", "t"), and(vec![Condition::from("t.id = u.team_id"), not(op::eq("t.deleted", &true))]))
+            .and_where(filter)
+            .and_where(op::gt("u.id", &10))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT u.id FROM \"user\" AS u LEFT JOIN \"team\" AS t ON (t.id = u.team_id) AND NOT (t.deleted = $1) WHERE (\"name\" LIKE $2 OR \"email\" LIKE $3) AND u.id > $4");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[true, \"a%\", \"b%\", 10]");
+    }
+    #[test]
+    fn test_select_builder_owned_parameters() {
+        use crate::postgres::expr::{col, bind};
+        // the values only live in this function, so they have to be owned by the builder
+        fn active_user(min_age: i32, name: &str) -> SqlSelect<'static> {
+            let mut sqlselect = SqlSelect::new();
+            sqlselect
+                .select("id")
+                .table("user")
+                .and_where(op::gte("age", ParameterValue::owned(min_age)))
+                .and_where(col("name").like(bind(ParameterValue::owned(format!("{}%", name)))));
+            sqlselect
+        }
+        let sqlselect = active_user(18, "a");
+        let qbuild = std::thread::spawn(move || sqlselect.build().unwrap()).join().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"user\" WHERE age >= $1 AND \"name\" LIKE $2");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[18, \"a%\"]");
+        assert_eq!(qbuild.params().len(), 2);
+    }
+    #[test]
+    fn test_select_builder_count() {
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select("u.id")
+            .select("u.name")
+            .table(("user", "u"))
+            .and_where(op::eq("u.status", &"active"))
+            .order("u.id", "DESC")
+            .limit_offset((10, 20));
+        let count = sqlselect.count().build().unwrap();
+        assert_eq!(count.query, "SELECT COUNT(*) FROM \"user\" AS u WHERE u.status = $1");
+        assert_eq!(format!("{:?}", count.parameters), "[\"active\"]");
+        // the original select is untouched
+        let qbuild = sqlselect.build().unwrap();
+        assert_eq!(qbuild.query, "SELECT u.id, u.name FROM \"user\" AS u WHERE u.status = $1 ORDER BY u.id DESC LIMIT 10 OFFSET 20");
+        let mut forked = sqlselect.clone();
+        forked.and_where(op::gt("u.id", &5));
+        assert_eq!(forked.build().unwrap().query, "SELECT u.id, u.name FROM \"user\" AS u WHERE u.status = $1 AND u.id > $2 ORDER BY u.id DESC LIMIT 10 OFFSET 20");
+
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .select_as(format_query("SUM(amount) + {}", vec![&1]), "total")
+            .table("payment")
+            .and_where(op::gt("amount", &0))
+            .group("user_id")
+            .order("total", "DESC")
+            .limit_offset(10);
+        let count = sqlselect.count().build().unwrap();
+        assert_eq!(count.query, "SELECT COUNT(*) FROM (SELECT SUM(amount) + $1 AS total FROM \"payment\" WHERE amount > $2 GROUP BY user_id) AS count_rows");
+        assert_eq!(format!("{:?}", count.parameters), "[1, 0]");
+    }
+    #[test]
+    fn test_select_builder_having() {
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("user_id")
+            .select_as("SUM(amount)", "total")
+            .table("payment")
+            .and_where(op::gt("amount", &0))
+            .group("user_id")
+            .and_having(op::gt("SUM(amount)", &100))
+            .and_having_open()
+            .or_having(op::lt("COUNT(*)", &5))
+            .or_having(op::gt("MAX(amount)", &50))
+            .having_close()
+            .order("total", "DESC")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT user_id, SUM(amount) AS total FROM \"payment\" WHERE amount > $1 GROUP BY user_id HAVING SUM(amount) > $2 AND (COUNT(*) < $3 OR MAX(amount) > $4) ORDER BY total DESC");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[0, 100, 5, 50]");
+
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("user_id")
+            .table("payment")
+            .group("user_id")
+            .and_having_open()
+            .build().unwrap_err();
+        assert!(matches!(error, Error::UnbalancedWhere { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Having));
+    }
+    #[test]
+    fn test_select_builder_distinct() {
+        use crate::postgres::expr::col;
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .distinct()
+            .select("status")
+            .table("user")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT DISTINCT status FROM \"user\"");
+
+        // latest payment of each user
+        let mut sqlselect = SqlSelect::new();
+        sqlselect
+            .distinct_on(["user_id", "currency"])
+            .select("user_id")
+            .select("currency")
+            .select("amount")
+            .table("payment")
+            .and_where(op::gt("amount", &0))
+            .order("currency", "ASC")
+            .order("user_id", "ASC")
+            .order("created_at", "DESC");
+        let qbuild = sqlselect.build().unwrap();
+        assert_eq!(qbuild.query, "SELECT DISTINCT ON (user_id, currency) user_id, currency, amount FROM \"payment\" WHERE amount > $1 ORDER BY currency ASC, user_id ASC, created_at DESC");
+        assert_eq!(sqlselect.count().build().unwrap().query, "SELECT COUNT(*) FROM (SELECT DISTINCT ON (user_id, currency) user_id, currency, amount FROM \"payment\" WHERE amount > $1) AS count_rows");
+
+        // a column from col() and the same column as a string
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .distinct_on(vec![col("user_id")])
+            .select("user_id")
+            .table("payment")
+            .order("user_id", "ASC")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT DISTINCT ON (\"user_id\") user_id FROM \"payment\" ORDER BY user_id ASC");
+
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .distinct_on(vec![format_query("LOWER(email) || {}", vec![&"@"])])
+            .select("id")
+            .table("user")
+            .order("created_at", "DESC")
+            .build().unwrap_err();
+        assert!(matches!(error, Error::DistinctOnMismatch { .. }));
+        assert_eq!(error.context().clause, Some(Clause::OrderBy));
+        assert_eq!(error.context().identifier.as_deref(), Some("created_at"));
+        assert_eq!(error.to_string(), "DISTINCT ON expressions must match the leading ORDER BY expressions [builder: SELECT, clause: ORDER BY, identifier: created_at]");
+
+        // the bad direction is the error, not the mismatch
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .distinct_on(["user_id"])
+            .select("user_id")
+            .table("payment")
+            .order("created_at", "SIDEWAYS")
+            .build().unwrap_err();
+        assert!(matches!(error, Error::InvalidOrder { .. }));
+        assert_eq!(error.context().clause, Some(Clause::OrderBy));
+    }
+    #[test]
+    fn test_select_builder_window() {
+        use crate::postgres::expr::{col, func, bind, window, FrameBound};
+        // leaderboard with a running total
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("team_id")
+            .select("score")
+            .select_as(func("rank", vec![]).over_named("w"), "position")
+            .select_as(func("SUM", vec![col("score")]).over(window()
+                .base("w")
+                .rows(FrameBound::UnboundedPreceding, FrameBound::CurrentRow)), "running_total")
+            .select_as(func("AVG", vec![col("score")]).over(window()
+                .partition_by(col("team_id"))
+                .order_by(col("played_at"), "ASC")
+                .rows(FrameBound::Preceding(bind(&2)), FrameBound::CurrentRow)), "moving_average")
+            .table("game")
+            .and_where(op::gt("score", &0))
+            .window("w", window().partition_by(col("team_id")).order_by(col("score"), "DESC"))
+            .order("team_id", "ASC")
+            .order("position", "ASC")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT team_id, score, rank() OVER w AS position, SUM(\"score\") OVER (w ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS running_total, AVG(\"score\") OVER (PARTITION BY \"team_id\" ORDER BY \"played_at\" ASC ROWS BETWEEN $1 PRECEDING AND CURRENT ROW) AS moving_average FROM \"game\" WHERE score > $2 WINDOW w AS (PARTITION BY \"team_id\" ORDER BY \"score\" DESC) ORDER BY team_id ASC, position ASC");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[2, 0]");
+    }
+    #[test]
+    fn test_select_builder_order() {
+        use crate::postgres::query_order::{Order, Nulls};
+        use crate::postgres::expr::{col, bind, lit, case};
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("ticket")
+            .and_where(op::eq("team_id", &1))
+            .order(case().when(col("status").eq(bind(&"open")), lit(0)).otherwise(lit(1)).end(), Order::Asc)
+            .order("priority", Order::Desc.nulls_last())
+            .order("due_at", (Order::Asc, Nulls::First))
+            .order("id", "desc")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE team_id = $1 ORDER BY CASE WHEN \"status\" = $2 THEN 0 ELSE 1 END ASC, priority DESC NULLS LAST, due_at ASC NULLS FIRST, id DESC");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, \"open\"]");
+
+        // the direction from a request is rejected instead of being written into the query
+        let direction = String::from("ASC, (SELECT password FROM user)");
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("id")
+            .table("ticket")
+            .order("id", direction.as_str())
+            .build().unwrap_err();
+        assert!(matches!(error, Error::InvalidOrder { .. }));
+        assert_eq!(error.context().clause, Some(Clause::OrderBy));
+        assert_eq!(error.context().identifier.as_deref(), Some(direction.as_str()));
+    }
+
+    #[test]
+    fn test_select_builder_ident() {
+        use crate::postgres::ident::Ident;
+        use crate::postgres::query_order::Order;
+        use crate::postgres::expr::{col, bind};
+        // sort and filter columns from a request
+        let sort = Ident::parse("\"createdAt\"").unwrap();
+        let filter = Ident::parse("T.Team_Id").unwrap();
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select(Ident::new("t.id"))
+            .select_as(Ident::new("t.name"), Ident::new("Name"))
+            .table((Ident::new("public.ticket"), "t"))
+            .and_where(col(filter).eq(bind(&1)))
+            .order(sort, Order::Desc)
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT \"t\".\"id\", \"t\".\"name\" AS \"Name\" FROM \"public\".\"ticket\" AS t WHERE \"t\".\"team_id\" = $1 ORDER BY \"createdAt\" DESC");
+        assert!(matches!(Ident::parse("id; DROP TABLE ticket"), Err(Error::InvalidIdentifier { .. })));
+    }
+
+    #[test]
+    fn test_select_builder_limit_offset() {
+        use crate::postgres::query_limit_offset::LimitOffset;
+        let (limit, offset) = (10i64, 20i64);
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("ticket")
+            .and_where(op::eq("team_id", &1))
+            .order("score", "DESC")
+            .limit_offset(LimitOffset::new().offset(&offset).fetch_first(&limit).with_ties())
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE team_id = $1 ORDER BY score DESC OFFSET $2 ROWS FETCH FIRST $3 ROWS WITH TIES");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 20, 10]");
+        let qbuild = sqlselect.limit_offset(LimitOffset::new().offset(&offset)).build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE team_id = $1 ORDER BY score DESC OFFSET $2");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, 20]");
+
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("id")
+            .table("ticket")
+            .limit_offset(LimitOffset::new().fetch_first(5).with_ties())
+            .build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Limit));
+    }
+
+    #[test]
+    fn test_select_builder_lock() {
+        use crate::postgres::query_lock::Lock;
+        use crate::postgres::ident::Ident;
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("j.id")
+            .table(("job", "j"))
+            .inner_join(("queue", "q"), format_query("q.id = j.queue_id", vec![]))
+            .and_where(op::eq("j.state", &"pending"))
+            .order("j.id", "ASC")
+            .limit_offset(10)
+            .lock(Lock::update().of("j").skip_locked())
+            .lock(Lock::key_share().of("q").nowait())
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT j.id FROM \"job\" AS j INNER JOIN \"queue\" AS q ON q.id = j.queue_id WHERE j.state = $1 ORDER BY j.id ASC LIMIT 10 FOR UPDATE OF j SKIP LOCKED FOR KEY SHARE OF q NOWAIT");
+        assert_eq!(sqlselect.count().build().unwrap().query, "SELECT COUNT(*) FROM \"job\" AS j INNER JOIN \"queue\" AS q ON q.id = j.queue_id WHERE j.state = $1");
+
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("queue_id")
+            .table("job")
+            .group("queue_id")
+            .lock(Lock::update())
+            .build().unwrap_err();
+        assert!(matches!(error, Error::LockNotAllowed { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Lock));
+        assert_eq!(error.context().identifier.as_deref(), Some("GROUP BY"));
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("queue_id")
+            .table("job")
+            .distinct()
+            .lock(Lock::update())
+            .build().unwrap_err();
+        assert_eq!(error.context().identifier.as_deref(), Some("DISTINCT"));
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("id")
+            .table("job")
+            .window("w", crate::postgres::expr::window().order_by("id", "ASC"))
+            .lock(Lock::update())
+            .build().unwrap_err();
+        assert!(matches!(error, Error::LockNotAllowed { .. }));
+        assert_eq!(error.context().identifier.as_deref(), Some("WINDOW"));
+        let mut sqlselect = SqlSelect::new();
+        let error = sqlselect
+            .select("id")
+            .table("job")
+            .lock(Lock::update().of("public.job"))
+            .build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Lock));
+
+        // OF is written like the alias, raw for a string and quoted for an Ident
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("U.id")
+            .table(("users", "U"))
+            .lock(Lock::update().of("U"))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT U.id FROM \"users\" AS U FOR UPDATE OF U");
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table(("users", Ident::new("U")))
+            .lock(Lock::update().of(Ident::new("U")))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"users\" AS \"U\" FOR UPDATE OF \"U\"");
+    }
+
+    #[test]
+    fn test_select_builder_seek_after() {
+        use crate::postgres::query_order::Order;
+        use crate::postgres::cursor::Cursor;
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("ticket")
+            .and_where(op::eq("team_id", &1))
+            .seek_after(&[("created_at", Order::Asc, ParameterValue::from(&"2020-01-01")), ("id", Order::Asc, ParameterValue::from(&10))], 20)
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE team_id = $1 AND (created_at, id) > ($2, $3) ORDER BY created_at ASC, id ASC LIMIT 20");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1, \"2020-01-01\", 10]");
+
+        // the values of the last row go to the client as a cursor and come back for the next page
+        let token = Cursor::new().push(&5).push(&"foo").push(&10).encode();
+        let cursor = Cursor::decode(&token).unwrap();
+        let keys = vec![
+            ("priority", Order::Desc, ParameterValue::owned(cursor.get::<i32>(0).unwrap())),
+            ("name", Order::Asc, ParameterValue::owned(cursor.get::<String>(1).unwrap())),
+            ("id", Order::Asc, ParameterValue::owned(cursor.get::<i32>(2).unwrap())),
+        ];
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("ticket")
+            .seek_after(&keys, 20)
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE (priority < $1 OR priority = $2 AND name > $3 OR priority = $4 AND name = $5 AND id > $6) ORDER BY priority DESC, name ASC, id ASC LIMIT 20");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[5, 5, \"foo\", 5, \"foo\", 10]");
+
+        let mut sqlselect = SqlSelect::new();
+        let qbuild = sqlselect
+            .select("id")
+            .table("ticket")
+            .and_where(op::eq("team_id", &1))
+            .seek_after(&[("id", Order::Desc, &10)], 20)
+            .build().unwrap();
+        assert_eq!(qbuild.query, "SELECT id FROM \"ticket\" WHERE team_id = $1 AND id < $2 ORDER BY id DESC LIMIT 20");
+    }
 }
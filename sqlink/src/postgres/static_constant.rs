@@ -1,3 +1,3 @@
-pub const TABLE_ESCAPE: &str = "\"";
 pub const FIELD_ESCAPE: &str = "\"";
 pub const PARAM_NOTATION: &str = "$";
+pub const MAX_PARAMETER: usize = 65535;
@@ -1,91 +1,130 @@
-use crate::error::Error;
-use crate::postgres::query_table::{QueryTables, QueryTable};
-use crate::postgres::query_field::{QueryWithParams, ParameterValueAsRef};
-use crate::postgres::query_token::{QueryTokens, QueryToken, FormatQueryTup};
+use crate::error::{Error, ErrorContext, BuilderKind, Clause};
+use crate::postgres::query_table::{QueryTables, QueryTable, QueryFroms, TableJoin};
+use crate::postgres::query_field::{QueryWithParams, QueryWithParamsLoc, ParameterValue, ParamIter};
+use crate::postgres::expr::Expr;
+use crate::postgres::query_token::{QueryTokens, QueryToken};
 use crate::postgres::query_where::{QueryWheres, WhereOperator};
 use crate::postgres::query_set::{QuerySets};
-use crate::postgres::query_return::{QueryReturns, QueryReturnField};
+use crate::postgres::ident::{Ident, Alias};
+use crate::postgres::query_return::QueryReturns;
+use crate::postgres::query_with::{QueryWiths, WithQuery};
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct SqlUpdate<'a> {
+    _withs: QueryWiths,
     _tables: QueryTables, // to support update tableA, tableB set ...
     _sets: QuerySets,
+    _froms: QueryFroms,
     _wheres: QueryWheres,
     _returns: QueryReturns,
-    _parameters: Vec<ParameterValueAsRef<'a>>,
+    _parameters: Vec<ParameterValue<'a>>,
 }
 
 impl<'a> SqlUpdate<'a> {
     pub fn new() -> SqlUpdate<'static> {
         SqlUpdate::default()
     }
-    pub fn build(&self) -> Result<QueryWithParams, Error> {
-        let mut param_iter = 1;
-        let built_for_table = self._tables.build(&mut param_iter)?;
-        let built_for_update = self._sets.build_for_update(&mut param_iter)?;
+    pub fn build(&self) -> Result<QueryWithParams<'a>, Error> {
+        let mut param_iter = ParamIter::default();
+        let built = self.build_loc(&mut param_iter)?;
+        Ok(QueryWithParams {
+            query: built.query,
+            parameters: built.parameters_loc.into_iter().map(|ploc| self._parameters[ploc].clone()).collect(),
+        })
+    }
+    pub(crate) fn build_loc(&self, param_iter: &mut ParamIter) -> Result<QueryWithParamsLoc, Error> {
+        let at = |clause: Clause| move |e: Error| e.within(BuilderKind::Update, clause);
         let mut vec: Vec<String> = Vec::new();
-        let mut p: Vec<ParameterValueAsRef> = Vec::new();
-        for ploc in built_for_table.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let mut p: Vec<usize> = Vec::new();
+        if self._withs.len() > 0 {
+            let built_for_with = self._withs.build(param_iter).map_err(at(Clause::With))?;
+            vec.push(built_for_with.query);
+            p.extend(built_for_with.parameters_loc);
         }
-        for ploc in built_for_update.parameters_loc {
-            p.push(self._parameters[ploc]);
+        let built_for_table = self._tables.build(param_iter).map_err(at(Clause::From))?;
+        if self._sets.len() == 0 {
+            return Err(Error::EmptySet { context: ErrorContext::default() }).map_err(at(Clause::Set));
         }
+        let built_for_update = self._sets.build_for_update(param_iter).map_err(at(Clause::Set))?;
+        p.extend(built_for_table.parameters_loc);
+        p.extend(built_for_update.parameters_loc);
         vec.push(format!("UPDATE {} SET {}", built_for_table.query, built_for_update.query));
-        if self._wheres.len() > 0 {
-            let built_for_where = self._wheres.build(&mut param_iter)?;
+        if !self._froms.is_empty() {
+            let built_for_from = self._froms.build(param_iter).map_err(at(Clause::From))?;
+            vec.push(format!("FROM {}", built_for_from.query));
+            p.extend(built_for_from.parameters_loc);
+        }
+        let built_for_where = self._froms.build_wheres(&self._wheres, param_iter).map_err(at(Clause::Where))?;
+        if !built_for_where.query.is_empty() {
             vec.push(format!("WHERE {}", built_for_where.query));
-            for ploc in built_for_where.parameters_loc {
-                p.push(self._parameters[ploc]);
-            }
+            p.extend(built_for_where.parameters_loc);
         }
         if self._returns.len() > 0 {
-            let built_for_return: String = self._returns.build()?;
-            vec.push(format!("RETURNING {}", built_for_return));
+            let built_for_return = self._returns.build(param_iter).map_err(at(Clause::Returning))?;
+            vec.push(format!("RETURNING {}", built_for_return.query));
+            p.extend(built_for_return.parameters_loc);
         }
-
-        Ok(QueryWithParams {
+        Ok(QueryWithParamsLoc {
             query: vec.join(" "),
-            parameters: p,
+            parameters_loc: p,
         })
     }
+    pub(crate) fn into_parts(self) -> (SqlUpdate<'static>, Vec<ParameterValue<'a>>) {
+        let update = SqlUpdate {
+            _withs: self._withs,
+            _tables: self._tables,
+            _sets: self._sets,
+            _froms: self._froms,
+            _wheres: self._wheres,
+            _returns: self._returns,
+            _parameters: Vec::new(),
+        };
+        (update, self._parameters)
+    }
     pub fn table<S: Into<QueryTable>>(&mut self, table: S) -> &mut Self {
         self._tables.push(table.into());
         self
     }
-    pub fn set<S: Into<String>, T>(&mut self, field: S, param: &'a T) -> &mut Self where T: postgres_types::ToSql + std::marker::Sync + 'a {
-        self._parameters.push(param);
+    pub fn set<S: Into<Ident>, P: Into<ParameterValue<'a>>>(&mut self, field: S, param: P) -> &mut Self {
+        self._parameters.push(param.into());
         self._sets.set((field.into(), QueryTokens(vec![QueryToken::ParameterLoc(self._parameters.len() - 1)])));
         self
     }
-    pub fn set_raw<S: Into<String>>(&mut self, field: S, tup: FormatQueryTup<'a>) -> &mut Self{
+    pub fn set_raw<S: Into<Ident>, E: Into<Expr<'a>>>(&mut self, field: S, expr: E) -> &mut Self {
+        let tup = expr.into().into_format_query();
         let len = self._parameters.len();
         self._parameters.extend(tup.1);
-        let qtokens = (tup.0).to_query_tokens(len);
+        let qtokens = (tup.0).into_query_tokens(len);
         self._sets.set((field.into(), qtokens));
         self
     }
-    pub fn returning<S: Into<QueryReturnField>>(&mut self, field: S) -> &mut Self {
-        self._returns.push(field.into());
+    pub fn returning<E: Into<Expr<'a>>>(&mut self, field: E) -> &mut Self {
+        self._returns.push_expr(field.into(), None, &mut self._parameters);
         self
     }
-    pub fn and_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn returning_as<E: Into<Expr<'a>>, T: Into<Alias>>(&mut self, field: E, alias: T) -> &mut Self {
+        self._returns.push_expr(field.into(), Some(alias.into().to_sql()), &mut self._parameters);
+        self
+    }
+    pub fn and_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::And);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
-    pub fn or_where(&mut self, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn or_where<E: Into<Expr<'a>>>(&mut self, expr: E) -> &mut Self {
+        let ftup = expr.into().into_condition();
         if self._wheres.len() > 0 {
             self._wheres.push(WhereOperator::Or);
         }
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
+        let qtokens = (ftup.0).into_query_tokens(len);
         self._wheres.extend(qtokens.into());
         self
     }
@@ -105,46 +144,62 @@ impl<'a> SqlUpdate<'a> {
     }
     pub fn where_close(&mut self) -> &mut Self {
         self._wheres.push(WhereOperator::Close);
-        // lets dont panic, if error database will panic
         self
     }
-    pub fn inner_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    /// FROM "table", the other tables that the where can refer to
+    pub fn from<S: Into<QueryTable>>(&mut self, table: S) -> &mut Self {
+        self._froms.push(table.into());
+        self
+    }
+    /// FROM the table with the ON condition in WHERE, or joined to the last table of from()
+    pub fn inner_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.inner_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::InnerJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn left_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    /// outer joins can only join the tables of from(), the build fails when there is none
+    pub fn left_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.left_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::LeftJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn right_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn right_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.right_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::RightJoin(query_table.into(), qtokens.into()));
         self
     }
-    pub fn full_join<S: Into<QueryTable>>(&mut self, query_table: S, ftup: FormatQueryTup<'a>) -> &mut Self {
+    pub fn full_join<S: Into<QueryTable>, E: Into<Expr<'a>>>(&mut self, query_table: S, on: E) -> &mut Self {
+        let ftup = on.into().into_condition();
         let len = self._parameters.len();
         self._parameters.extend(ftup.1);
-        let qtokens = (ftup.0).to_query_tokens(len);
-        self._tables.full_join(query_table.into());
-        self._tables.on(qtokens.into());
+        let qtokens = (ftup.0).into_query_tokens(len);
+        self._froms.join(TableJoin::FullJoin(query_table.into(), qtokens.into()));
         self
     }
 }
 
+impl<'a> WithQuery<'a> for SqlUpdate<'a> {
+    fn withs_mut(&mut self) -> (&mut QueryWiths, &mut Vec<ParameterValue<'a>>) {
+        (&mut self._withs, &mut self._parameters)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::postgres::query_token::{format_query};
+    use crate::postgres::query_subquery::subquery;
+    use crate::postgres::select_builder::SqlSelect;
+    use crate::postgres::insert_builder::SqlInsert;
+    use crate::postgres::op;
     use super::*;
     #[test]
     fn test_update_builder_1() {
@@ -158,4 +213,138 @@ mod tests {
         assert_eq!(qbuild.query, "UPDATE \"user\" SET \"age\"=$1,\"name\"=LOWER($2) WHERE id = $3");
         assert_eq!(format!("{:?}", qbuild.parameters), "[1337, \"foo\", 1]");
     }
+
+    #[test]
+    fn test_update_builder_from() {
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .table(("account", "a"))
+            .set_raw("balance", format_query("a.balance + p.amount", vec![]))
+            .from(("payment", "p"))
+            .left_join(("refund", "r"), format_query("r.payment_id = p.id", vec![]))
+            .and_where(format_query("p.account_id = a.id", vec![]))
+            .and_where(op::eq("p.batch", &7))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "UPDATE \"account\" AS a SET \"balance\"=a.balance + p.amount FROM \"payment\" AS p LEFT JOIN \"refund\" AS r ON r.payment_id = p.id WHERE p.account_id = a.id AND p.batch = $1");
+
+        // an inner join on the updated table is written as FROM with the ON condition in WHERE
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .table(("account", "a"))
+            .set("active", &false)
+            .inner_join(("ban", "b"), op::eq("b.level", &3))
+            .inner_join(("person", "p"), format_query("p.id = b.person_id", vec![]))
+            .and_where(format_query("b.account_id = a.id", vec![]))
+            .or_where(op::eq("a.id", &1))
+            .returning("a.id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "UPDATE \"account\" AS a SET \"active\"=$1 FROM \"ban\" AS b,\"person\" AS p WHERE (b.level = $2) AND (p.id = b.person_id) AND (b.account_id = a.id OR a.id = $3) RETURNING a.id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[false, 3, 1]");
+
+        // every join before from() is its own table, as each one can refer to the updated table
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .table(("account", "a"))
+            .set("active", &false)
+            .inner_join(("ban", "b"), format_query("b.account_id = a.id", vec![]))
+            .inner_join(("person", "p"), format_query("p.id = a.person_id", vec![]))
+            .from(("team", "t"))
+            .inner_join(("league", "l"), format_query("l.id = t.league_id", vec![]))
+            .and_where(format_query("t.id = p.team_id", vec![]))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "UPDATE \"account\" AS a SET \"active\"=$1 FROM \"ban\" AS b,\"person\" AS p,\"team\" AS t INNER JOIN \"league\" AS l ON l.id = t.league_id WHERE (b.account_id = a.id) AND (p.id = a.person_id) AND (t.id = p.team_id)");
+
+        // an OR in the ON stays inside its parentheses
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .table(("account", "a"))
+            .set("active", &false)
+            .inner_join(("ban", "b"), format_query("b.account_id = a.id OR b.email = a.email", vec![]))
+            .and_where(op::eq("a.id", &1))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "UPDATE \"account\" AS a SET \"active\"=$1 FROM \"ban\" AS b WHERE (b.account_id = a.id OR b.email = a.email) AND (a.id = $2)");
+
+        let mut sqlupdate = SqlUpdate::new();
+        let error = sqlupdate
+            .table("account")
+            .set("active", &false)
+            .left_join("ban", format_query("ban.account_id = account.id", vec![]))
+            .build().unwrap_err();
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.context().clause, Some(Clause::Join));
+        assert_eq!(error.context().identifier.as_deref(), Some("ban"));
+    }
+
+    #[test]
+    fn test_update_builder_subquery() {
+        let mut sub_set = SqlSelect::new();
+        sub_set
+            .select("MAX(score)")
+            .table("score")
+            .and_where(format_query("score.user_id = \"user\".id AND score.season = {}", vec![&3]));
+        let mut sub_where = SqlSelect::new();
+        sub_where
+            .select("user_id")
+            .table("ban")
+            .and_where(format_query("level > {}", vec![&2]));
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .table("user")
+            .set("age", &1337)
+            .set_raw("best_score", subquery(sub_set))
+            .and_where(op::not_in_select("id", sub_where))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "UPDATE \"user\" SET \"age\"=$1,\"best_score\"=(SELECT MAX(score) FROM \"score\" WHERE score.user_id = \"user\".id AND score.season = $2) WHERE id NOT IN (SELECT user_id FROM \"ban\" WHERE level > $3)");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[1337, 3, 2]");
+    }
+
+    #[test]
+    fn test_update_builder_with() {
+        let mut sql_insert = SqlInsert::new();
+        sql_insert
+            .table("audit")
+            .set("action", &"rename")
+            .returning("id");
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .with("audit_row", sql_insert)
+            .table("user")
+            .set("name", &"foo")
+            .set_raw("audit_id", format_query("(SELECT id FROM audit_row)", vec![]))
+            .and_where(format_query("id = {}", vec![&1]))
+            .returning("id")
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH audit_row AS (INSERT INTO \"audit\"(\"action\") VALUES ($1) RETURNING id) UPDATE \"user\" SET \"name\"=$2,\"audit_id\"=(SELECT id FROM audit_row) WHERE id = $3 RETURNING id");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"rename\", \"foo\", 1]");
+    }
+
+    #[test]
+    fn test_update_builder_returning_expr() {
+        use crate::postgres::expr::{col, bind};
+        use crate::postgres::ident::Ident;
+        let mut sql_select = SqlSelect::new();
+        sql_select.select("id").table("team").and_where(format_query("code = {}", vec![&"a"]));
+        let mut sqlupdate = SqlUpdate::new();
+        let qbuild = sqlupdate
+            .with(Ident::new("Team"), sql_select)
+            .table(("user", Ident::new("U")))
+            .set("score", &1)
+            .and_where(format_query("team_id IN (SELECT id FROM \"Team\")", vec![]))
+            .returning(col("U.id"))
+            .returning_as(col("U.score") * bind(&10), Ident::new("Scaled"))
+            .build().unwrap();
+        assert_eq!(qbuild.query, "WITH \"Team\" AS (SELECT id FROM \"team\" WHERE code = $1) UPDATE \"user\" AS \"U\" SET \"score\"=$2 WHERE team_id IN (SELECT id FROM \"Team\") RETURNING \"U\".\"id\", \"U\".\"score\" * $3 AS \"Scaled\"");
+        assert_eq!(format!("{:?}", qbuild.parameters), "[\"a\", 1, 10]");
+    }
+
+    #[test]
+    fn test_update_builder_empty_set() {
+        let mut sqlupdate = SqlUpdate::new();
+        sqlupdate
+            .table("user")
+            .and_where(format_query("id = {}", vec![&1]));
+        let error = sqlupdate.build().unwrap_err();
+        assert!(matches!(error, Error::EmptySet { .. }));
+        assert_eq!(error.to_string(), "statement requires at least 1 set [builder: UPDATE, clause: SET]");
+    }
 }